@@ -38,7 +38,7 @@ fn fuel_required(mass: i64) -> Option<i64> {
     if ret >= 0 {
         return Some(ret);
     }
-    return None;
+    None
 }
 
 fn fuel_required_with_fuel(mass: i64) -> i64 {
@@ -2,12 +2,14 @@ use std::error;
 use std::fs::File;
 use std::io::Read;
 
+use intcode::{IntCodeComputer, Program};
+
 type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
 
 fn main() -> Result<()> {
     let contents = get_contents("input");
 
-    let result = run(&replace(&contents, 12, 2));
+    let result = run(&replace(&contents, 12, 2))?;
 
     dbg!(get_output(&result));
 
@@ -15,7 +17,7 @@ fn main() -> Result<()> {
 
     for noun in 0..99 {
         for verb in 0..99 {
-            if get_output(&run(&replace(&contents, noun, verb))) == 19690720 {
+            if get_output(&run(&replace(&contents, noun, verb))?) == 19690720 {
                 dbg!(100 * noun + verb);
                 done = true;
                 break;
@@ -44,28 +46,14 @@ fn replace(program: &str, noun: usize, verb: usize) -> String {
         .join(",")
 }
 
-fn run(program: &str) -> String {
-    let mut instructions: Vec<usize> = program.split(",").map(|x| x.parse().unwrap()).collect();
-    let mut counter = 0;
-    while instructions[counter] != 99 {
-        let instruction = instructions[counter];
-        let res;
-        if instruction == 1 {
-            res = instructions[instructions[counter + 1]] + instructions[instructions[counter + 2]];
-        } else if instruction == 2 {
-            res = instructions[instructions[counter + 1]] * instructions[instructions[counter + 2]];
-        } else {
-            panic!()
-        }
-        let address = instructions[counter + 3];
-        instructions[address] = res;
-        counter += 4;
-    }
-    instructions
-        .iter()
-        .map(|i| i.to_string())
+fn run(program: &str) -> Result<String> {
+    let program: Program = program.parse()?;
+    let mut computer = IntCodeComputer::new(&program);
+    computer.run_to_halt(&[])?;
+    Ok((0..program.words().len() as i64)
+        .map(|i| computer.read(i).to_string())
         .collect::<Vec<String>>()
-        .join(",")
+        .join(","))
 }
 
 fn get_contents(filename: &str) -> String {
@@ -84,9 +72,11 @@ mod tests {
 
     #[test]
     fn part1() {
-        assert!(run("1,9,10,3,2,3,11,0,99,30,40,50") == "3500,9,10,70,2,3,11,0,99,30,40,50");
-        assert!(run("1,0,0,0,99") == "2,0,0,0,99");
-        assert!(run("2,3,0,3,99") == "2,3,0,6,99");
-        assert!(run("1,1,1,4,99,5,6,0,99") == "30,1,1,4,2,5,6,0,99");
+        assert!(
+            run("1,9,10,3,2,3,11,0,99,30,40,50").unwrap() == "3500,9,10,70,2,3,11,0,99,30,40,50"
+        );
+        assert!(run("1,0,0,0,99").unwrap() == "2,0,0,0,99");
+        assert!(run("2,3,0,3,99").unwrap() == "2,3,0,6,99");
+        assert!(run("1,1,1,4,99,5,6,0,99").unwrap() == "30,1,1,4,2,5,6,0,99");
     }
 }
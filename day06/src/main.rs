@@ -39,25 +39,25 @@ fn min_transfers(input: &str) -> usize {
 
 fn find_path(graph: &TreeGraph, destination: &str) -> Option<Vec<String>> {
     let mut paths: Vec<Option<Vec<String>>> = Vec::new();
-    if graph.children.len() == 0 {
+    if graph.children.is_empty() {
         return None;
     }
     for child in &(graph.children) {
         if child.node_name == destination {
             paths.push(Some(vec![destination.to_string()]));
         } else {
-            paths.push(find_path(&child, destination));
+            paths.push(find_path(child, destination));
         }
     }
     let mut paths: Vec<Vec<String>> = paths.into_iter().flatten().collect();
-    if paths.len() == 0 {
+    if paths.is_empty() {
         return None;
     }
     if paths.len() != 1 {
         panic!();
     }
     let path = &mut paths[0];
-    path.push((&(graph.node_name)).to_string());
+    path.push((graph.node_name).to_string());
     Some(path.to_vec())
 }
 
@@ -85,13 +85,10 @@ fn create_tree_graph(root_key: &str, orbit_map: &HashMap<&str, Vec<&str>>) -> Tr
         children: Vec::new(),
     };
 
-    match orbit_map.get(root_key) {
-        Some(children) => {
-            for child in children {
-                graph.children.push(create_tree_graph(child, orbit_map));
-            }
+    if let Some(children) = orbit_map.get(root_key) {
+        for child in children {
+            graph.children.push(create_tree_graph(child, orbit_map));
         }
-        None => {}
     }
 
     graph
@@ -108,7 +105,7 @@ fn get_orbit_map(input: &str) -> HashMap<&str, Vec<&str>> {
 
     for line in input.lines() {
         let relationship = line.split(")").collect::<Vec<&str>>();
-        let entry = ret.entry(relationship[0]).or_insert(Vec::new());
+        let entry = ret.entry(relationship[0]).or_default();
         entry.push(relationship[1])
     }
 
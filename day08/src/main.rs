@@ -1,4 +1,3 @@
-use std::convert::TryFrom;
 use std::error;
 use std::fs::File;
 use std::io::Read;
@@ -27,10 +26,7 @@ fn main() -> Result<()> {
     let mut index = nlayers + 1;
 
     for i in 0..nlayers {
-        let nzeros = data
-            .slice(s![i, .., ..])
-            .mapv(|a| u32::try_from(a == 0).unwrap())
-            .sum();
+        let nzeros = data.slice(s![i, .., ..]).mapv(|a| u32::from(a == 0)).sum();
         if nzeros < min_zeros {
             min_zeros = dbg!(nzeros);
             index = dbg!(i);
@@ -39,18 +35,9 @@ fn main() -> Result<()> {
 
     let sl = data.slice(s![index, .., ..]);
 
-    dbg!(
-        sl.mapv(|a| u32::try_from(a == 1).unwrap()).sum()
-            * sl.mapv(|a| u32::try_from(a == 2).unwrap()).sum()
-    );
+    dbg!(sl.mapv(|a| u32::from(a == 1)).sum() * sl.mapv(|a| u32::from(a == 2)).sum());
 
-    let image = data.fold_axis(Axis(0), 2, |acc, x| {
-        if *acc != 2 {
-            return *acc;
-        } else {
-            return *x;
-        }
-    });
+    let image = data.fold_axis(Axis(0), 2, |acc, x| if *acc != 2 { *acc } else { *x });
 
     println!("{}", image);
 
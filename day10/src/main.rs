@@ -1,7 +1,6 @@
 use std::fs::File;
 use std::io::Read;
 
-use indoc::indoc;
 use ordered_float::OrderedFloat;
 use std::collections::HashMap;
 
@@ -55,10 +54,10 @@ fn get_num_can_see(
     let mut num_can_see = 0;
     for p in asteroid_positions {
         let angle = OrderedFloat(((position.1 - p.1) as f64).atan2((position.0 - p.0) as f64));
-        if !seen_angles.contains_key(&angle) {
+        seen_angles.entry(angle).or_insert_with(|| {
             num_can_see += 1;
-            seen_angles.insert(angle, p);
-        }
+            p
+        });
     }
     (num_can_see, seen_angles)
 }
@@ -75,11 +74,9 @@ fn vaporize(
     let mut seen_angles: HashMap<OrderedFloat<f64>, (i64, i64)> = HashMap::new();
     for p in asteroid_positions.iter() {
         let angle = OrderedFloat(((position.1 - p.1) as f64).atan2((position.0 - p.0) as f64));
-        if !seen_angles.contains_key(&angle) {
-            seen_angles.insert(angle, *p);
-        }
+        seen_angles.entry(angle).or_insert(*p);
     }
-    let mut angles: Vec<OrderedFloat<f64>> = seen_angles.keys().map(|x| *x).collect();
+    let mut angles: Vec<OrderedFloat<f64>> = seen_angles.keys().copied().collect();
     angles.sort();
     let mut ret_angle = -4.0;
     for angle in angles {
@@ -113,6 +110,7 @@ fn get_maxnum(map: &str) -> (usize, (i64, i64)) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use indoc::indoc;
 
     #[test]
     fn test() {
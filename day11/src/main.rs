@@ -4,6 +4,8 @@ use std::error;
 use std::fs::File;
 use std::io::Read;
 
+use intcode::{IntCodeComputer, RunState};
+
 type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
 
 fn main() -> Result<()> {
@@ -13,14 +15,10 @@ fn main() -> Result<()> {
     let mut robot = Robot {
         position: (0, 0),
         direction: Direction::North,
-        computer: IntCodeComputer {
-            instructions: get_instructions(&contents),
-            counter: 0,
-            relative_base: 0,
-        },
+        computer: contents.parse()?,
     };
 
-    let mut input = vec![1];
+    let mut input = 1;
     let mut panel_colors: HashMap<(i64, i64), bool> = HashMap::new();
     let mut painted_panels: HashSet<(i64, i64)> = HashSet::new();
     let mut minx: i64 = 0;
@@ -31,18 +29,17 @@ fn main() -> Result<()> {
     panel_colors.insert((0, 0), true);
 
     loop {
-        let output = robot.computer.run(&mut input);
-        if output.len() == 0 {
-            if robot.computer.instructions[&robot.computer.counter] != 99 {
-                panic!();
-            }
-            break;
-        }
-        if output.len() != 2 {
-            panic!();
-        }
+        robot.computer.push_input(input);
+        let mut outputs = robot.computer.outputs();
+        let [color, turn] = match outputs.next_n() {
+            Some(frame) => frame,
+            None => match outputs.stop().unwrap()? {
+                RunState::Halted => break,
+                _ => panic!(),
+            },
+        };
         let entry = panel_colors.entry(robot.position).or_insert(false);
-        match output[0] {
+        match color {
             0 => *entry = false,
             1 => {
                 *entry = true;
@@ -50,7 +47,7 @@ fn main() -> Result<()> {
             }
             _ => panic!(),
         }
-        match output[1] {
+        match turn {
             0 => robot.direction = left(robot.direction),
             1 => robot.direction = right(robot.direction),
             _ => panic!(),
@@ -74,8 +71,8 @@ fn main() -> Result<()> {
             maxy = robot.position.1
         }
         input = match *panel_colors.entry(robot.position).or_insert(false) {
-            true => vec![1],
-            false => vec![0],
+            true => 1,
+            false => 0,
         }
     }
 
@@ -128,152 +125,6 @@ fn right(d: Direction) -> Direction {
     }
 }
 
-fn get_instructions(program: &str) -> HashMap<i64, i64> {
-    let instructions: Vec<i64> = program.split(",").map(|x| x.parse().unwrap()).collect();
-    let mut h_instructions: HashMap<i64, i64> = HashMap::new();
-    for (i, ins) in instructions.iter().enumerate() {
-        h_instructions.insert(i as i64, *ins);
-    }
-    h_instructions
-}
-
-#[derive(Debug)]
-struct IntCodeComputer {
-    instructions: HashMap<i64, i64>,
-    counter: i64,
-    relative_base: i64,
-}
-
-impl IntCodeComputer {
-    fn run(&mut self, input: &mut Vec<i64>) -> Vec<i64> {
-        let mut output: Vec<i64> = Vec::new();
-        while self.instructions[&self.counter] != 99 {
-            let instruction = self.instructions[&self.counter];
-            let mut instruction = instruction
-                .to_string()
-                .chars()
-                .map(|x| x.to_digit(10).unwrap())
-                .collect::<Vec<u32>>();
-            while instruction.len() < 5 {
-                instruction.insert(0, 0);
-            }
-            let opcode = 10 * instruction[3] + instruction[4];
-            let modes = &instruction[..3];
-            let instruction_length;
-            match opcode {
-                1 => {
-                    let (args, addresses) = self.get_args(modes, 3);
-                    let entry = self.instructions.entry(addresses[2]).or_insert(0);
-                    *entry = args[0] + args[1];
-                    instruction_length = 4;
-                }
-                2 => {
-                    let (args, addresses) = self.get_args(modes, 3);
-                    let entry = self.instructions.entry(addresses[2]).or_insert(0);
-                    *entry = args[0] * args[1];
-                    instruction_length = 4;
-                }
-                3 => {
-                    let (_, addresses) = self.get_args(modes, 1);
-                    let entry = self.instructions.entry(addresses[0]).or_insert(0);
-                    *entry = input.pop().unwrap();
-                    instruction_length = 2;
-                }
-                4 => {
-                    let (args, _) = self.get_args(modes, 1);
-                    output.push(args[0]);
-                    if output.len() == 2 {
-                        self.counter += 2;
-                        return output;
-                    }
-                    instruction_length = 2;
-                }
-                5 => {
-                    let (args, _) = self.get_args(modes, 2);
-                    if args[0] != 0 {
-                        self.counter = args[1];
-                        instruction_length = 0;
-                    } else {
-                        instruction_length = 3;
-                    }
-                }
-                6 => {
-                    let (args, _) = self.get_args(modes, 2);
-                    if args[0] == 0 {
-                        self.counter = args[1];
-                        instruction_length = 0;
-                    } else {
-                        instruction_length = 3;
-                    }
-                }
-                7 => {
-                    let (args, addresses) = self.get_args(modes, 3);
-                    let res = match args[0] < args[1] {
-                        true => 1,
-                        false => 0,
-                    };
-                    let entry = self.instructions.entry(addresses[2]).or_insert(0);
-                    *entry = res;
-                    instruction_length = 4;
-                }
-                8 => {
-                    let (args, addresses) = self.get_args(modes, 3);
-                    let res = match args[0] == args[1] {
-                        true => 1,
-                        false => 0,
-                    };
-                    let entry = self.instructions.entry(addresses[2]).or_insert(0);
-                    *entry = res;
-                    instruction_length = 4;
-                }
-                9 => {
-                    let (args, _) = self.get_args(modes, 1);
-                    self.relative_base += args[0];
-                    instruction_length = 2;
-                }
-                _ => panic!(),
-            }
-            self.counter += instruction_length;
-        }
-        output
-    }
-
-    fn get_args(&mut self, modes: &[u32], num_args: usize) -> (Vec<i64>, Vec<i64>) {
-        let mut args: Vec<i64> = Vec::new();
-        let mut addresses: Vec<i64> = Vec::new();
-        for arg_count in 0..num_args {
-            let mut address = self.instructions[&(self.counter + arg_count as i64 + 1)];
-            let arg;
-            let mode = match arg_count {
-                0 => modes[2],
-                1 => modes[1],
-                2 => {
-                    if modes[0] == 1 {
-                        panic!()
-                    };
-                    modes[0]
-                }
-                _ => {
-                    panic!();
-                }
-            };
-            if mode == 0 {
-                arg = self.instructions.entry(address).or_insert(0);
-            } else if mode == 1 {
-                arg = &mut address;
-            } else if mode == 2 {
-                address = address + self.relative_base;
-                arg = self.instructions.entry(address).or_insert(0);
-            } else {
-                panic!();
-            }
-            args.push(*arg);
-            addresses.push(address);
-        }
-        (args, addresses)
-    }
-}
-
 fn get_contents(filename: &str) -> String {
     let mut f = File::open(filename).expect("file not found");
 
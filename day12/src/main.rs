@@ -33,15 +33,15 @@ fn main() {
 
     let init_system = System::new(&positions.clone());
 
-    let mut periods = vec![0, 0, 0];
+    let mut periods = [0, 0, 0];
 
-    for ax in 0..3 {
+    for (ax, period) in periods.iter_mut().enumerate() {
         let mut system = System::new(&positions);
         system.update();
         while !system.state_equal(&init_system, ax) {
             system.update();
         }
-        periods[ax] = system.time + 1;
+        *period = system.time + 1;
     }
 
     dbg!(periods.iter().fold(1, |acc, x| { lcm(acc, *x) }));
@@ -149,6 +149,7 @@ impl System {
         self.time += 1;
     }
 
+    #[allow(dead_code)]
     fn print_energy(&self) {
         let mut tesum = 0;
         println!("Energy after {} steps:", self.time);
@@ -168,6 +169,7 @@ impl System {
         println!("Sum of total energy: {}", tesum);
     }
 
+    #[allow(dead_code)]
     fn print_state(&self) {
         println!("After {} steps:", self.time);
         for moon in self.moons.iter() {
@@ -1,13 +1,25 @@
 use std::collections::HashMap;
+use std::env;
+use std::error;
 use std::fs::File;
-use std::io::Read;
+use std::io::{LineWriter, Read};
 use std::thread::sleep;
 use std::time::Duration;
 
-fn main() {
+use intcode::{IntCodeComputer, ParseError};
+
+type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
+
+fn main() -> Result<()> {
     let program = get_contents("input");
 
-    let mut game = ArcadeCabinet::from_program(&program);
+    let mut game = ArcadeCabinet::from_program(&program)?;
+
+    // Set INTCODE_TRACE to a filename to record the game's execution.
+    if let Ok(filename) = env::var("INTCODE_TRACE") {
+        game.computer
+            .trace_to(LineWriter::new(File::create(filename).unwrap()));
+    }
 
     let mut input: Vec<i64> = vec![];
 
@@ -36,25 +48,27 @@ struct ArcadeCabinet {
 }
 
 impl ArcadeCabinet {
-    fn from_program(program: &str) -> ArcadeCabinet {
-        ArcadeCabinet {
-            computer: IntCodeComputer::from_program(program),
+    fn from_program(program: &str) -> std::result::Result<ArcadeCabinet, ParseError> {
+        Ok(ArcadeCabinet {
+            computer: program.parse()?,
             screen: HashMap::new(),
             score: 0,
-        }
+        })
     }
 
     fn run(&mut self, init_input: Vec<i64>) -> String {
-        let output = self.computer.run(&init_input);
-
-        for tile_data in output.chunks(3) {
-            if (tile_data[0], tile_data[1]) == (-1, 0) {
-                self.score = tile_data[2];
+        for value in init_input {
+            self.computer.push_input(value);
+        }
+        let mut outputs = self.computer.outputs();
+        while let Some([x, y, tile]) = outputs.next_n() {
+            if (x, y) == (-1, 0) {
+                self.score = tile;
                 continue;
             }
-            self.screen
-                .insert((tile_data[0], tile_data[1]), tile_data[2]);
+            self.screen.insert((x, y), tile);
         }
+        outputs.stop().unwrap().unwrap();
 
         let mut maxx = 0i64;
         let mut minx = 0i64;
@@ -95,7 +109,7 @@ impl ArcadeCabinet {
                     output_screen.push(' ')
                 }
             }
-            output_screen.push_str("\n");
+            output_screen.push('\n');
         }
         output_screen
     }
@@ -110,156 +124,6 @@ impl ArcadeCabinet {
     }
 }
 
-#[derive(Debug)]
-struct IntCodeComputer {
-    instructions: HashMap<i64, i64>,
-    counter: i64,
-    relative_base: i64,
-}
-
-impl IntCodeComputer {
-    fn run(&mut self, input: &Vec<i64>) -> Vec<i64> {
-        let mut input = input.clone();
-        let mut output: Vec<i64> = Vec::new();
-        while self.instructions[&self.counter] != 99 {
-            let instruction = self.instructions[&self.counter];
-            let mut instruction = instruction
-                .to_string()
-                .chars()
-                .map(|x| x.to_digit(10).unwrap())
-                .collect::<Vec<u32>>();
-            while instruction.len() < 5 {
-                instruction.insert(0, 0);
-            }
-            let opcode = 10 * instruction[3] + instruction[4];
-            let modes = &instruction[..3];
-            let instruction_length;
-            match opcode {
-                1 => {
-                    let (args, addresses) = self.get_args(modes, 3);
-                    let entry = self.instructions.entry(addresses[2]).or_insert(0);
-                    *entry = args[0] + args[1];
-                    instruction_length = 4;
-                }
-                2 => {
-                    let (args, addresses) = self.get_args(modes, 3);
-                    let entry = self.instructions.entry(addresses[2]).or_insert(0);
-                    *entry = args[0] * args[1];
-                    instruction_length = 4;
-                }
-                3 => {
-                    let (_, addresses) = self.get_args(modes, 1);
-                    let entry = self.instructions.entry(addresses[0]).or_insert(0);
-                    match input.pop() {
-                        Some(value) => *entry = value,
-                        None => return output,
-                    }
-                    instruction_length = 2;
-                }
-                4 => {
-                    let (args, _) = self.get_args(modes, 1);
-                    output.push(args[0]);
-                    instruction_length = 2;
-                }
-                5 => {
-                    let (args, _) = self.get_args(modes, 2);
-                    if args[0] != 0 {
-                        self.counter = args[1];
-                        instruction_length = 0;
-                    } else {
-                        instruction_length = 3;
-                    }
-                }
-                6 => {
-                    let (args, _) = self.get_args(modes, 2);
-                    if args[0] == 0 {
-                        self.counter = args[1];
-                        instruction_length = 0;
-                    } else {
-                        instruction_length = 3;
-                    }
-                }
-                7 => {
-                    let (args, addresses) = self.get_args(modes, 3);
-                    let res = match args[0] < args[1] {
-                        true => 1,
-                        false => 0,
-                    };
-                    let entry = self.instructions.entry(addresses[2]).or_insert(0);
-                    *entry = res;
-                    instruction_length = 4;
-                }
-                8 => {
-                    let (args, addresses) = self.get_args(modes, 3);
-                    let res = match args[0] == args[1] {
-                        true => 1,
-                        false => 0,
-                    };
-                    let entry = self.instructions.entry(addresses[2]).or_insert(0);
-                    *entry = res;
-                    instruction_length = 4;
-                }
-                9 => {
-                    let (args, _) = self.get_args(modes, 1);
-                    self.relative_base += args[0];
-                    instruction_length = 2;
-                }
-                _ => panic!(),
-            }
-            self.counter += instruction_length;
-        }
-        output
-    }
-
-    fn get_args(&mut self, modes: &[u32], num_args: usize) -> (Vec<i64>, Vec<i64>) {
-        let mut args: Vec<i64> = Vec::new();
-        let mut addresses: Vec<i64> = Vec::new();
-        for arg_count in 0..num_args {
-            let mut address = self.instructions[&(self.counter + arg_count as i64 + 1)];
-            let arg;
-            let mode = match arg_count {
-                0 => modes[2],
-                1 => modes[1],
-                2 => {
-                    if modes[0] == 1 {
-                        panic!()
-                    };
-                    modes[0]
-                }
-                _ => {
-                    panic!();
-                }
-            };
-            if mode == 0 {
-                arg = self.instructions.entry(address).or_insert(0);
-            } else if mode == 1 {
-                arg = &mut address;
-            } else if mode == 2 {
-                address = address + self.relative_base;
-                arg = self.instructions.entry(address).or_insert(0);
-            } else {
-                panic!();
-            }
-            args.push(*arg);
-            addresses.push(address);
-        }
-        (args, addresses)
-    }
-
-    fn from_program(program: &str) -> IntCodeComputer {
-        let instructions: Vec<i64> = program.split(",").map(|x| x.parse().unwrap()).collect();
-        let mut h_instructions: HashMap<i64, i64> = HashMap::new();
-        for (i, ins) in instructions.iter().enumerate() {
-            h_instructions.insert(i as i64, *ins);
-        }
-        IntCodeComputer {
-            instructions: h_instructions,
-            counter: 0,
-            relative_base: 0,
-        }
-    }
-}
-
 fn get_contents(filename: &str) -> String {
     let mut f = File::open(filename).expect("file not found");
 
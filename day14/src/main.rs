@@ -57,18 +57,12 @@ impl Reaction {
             products.insert(name.to_string(), amount.parse::<i64>().unwrap());
         }
 
-        Reaction {
-            reagants: reagants,
-            products: products,
-        }
+        Reaction { reagants, products }
     }
 }
 
 fn get_num_ore(reaction_text: &str, num_fuel: i64) -> i64 {
-    let reactions: Vec<Reaction> = reaction_text
-        .lines()
-        .map(|x| Reaction::from_string(x))
-        .collect();
+    let reactions: Vec<Reaction> = reaction_text.lines().map(Reaction::from_string).collect();
 
     let mut elements: HashMap<String, i64> = HashMap::new();
     elements.insert("FUEL".to_string(), num_fuel);
@@ -107,6 +101,7 @@ fn get_num_ore(reaction_text: &str, num_fuel: i64) -> i64 {
     *elements.get("ORE").unwrap()
 }
 
+#[cfg(test)]
 mod tests {
     #[test]
     fn test() {
@@ -76,6 +76,7 @@ fn repeat10000(input: Vec<i32>) -> Vec<i32> {
         .collect::<Vec<i32>>()
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -98,7 +99,7 @@ mod tests {
                 == get_sequence("52432133")
         );
         let seq = repeat10000(get_sequence("03036732577212944063491565474664"));
-        let offset = 0303673;
+        let offset = 303673;
         assert!(fft_fast(&seq[offset..], 100)[..8].to_vec() == get_sequence("84462026"));
     }
 }
@@ -4,20 +4,24 @@ fn main() {
     println!("Hello, world!");
 }
 
+// Not used outside the test until the solution is written.
+#[allow(dead_code)]
 #[derive(Debug)]
 struct Map<'a> {
     tiles: HashMap<CoordOrName, MapTile<'a>>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, PartialEq, Eq, Hash)]
 struct CoordOrName {
     coord: Option<(i64, i64)>,
     name: Option<String>,
 }
 
+#[allow(dead_code)]
 impl Map<'_> {
-    fn from_map_str(map_str: &str) -> Map {
-        let mut map: Map = Map {
+    fn from_map_str(_map_str: &str) -> Map<'_> {
+        let map: Map = Map {
             tiles: HashMap::new(),
         };
 
@@ -25,6 +29,7 @@ impl Map<'_> {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 struct MapTile<'a> {
     name: CoordOrName,
@@ -36,6 +41,7 @@ struct MapTile<'a> {
     ),
 }
 
+#[cfg(test)]
 mod tests {
     #[test]
     fn test() {
@@ -65,6 +71,6 @@ mod tests {
                           Z     "
         );
 
-        let map: Map = Map::from_map_str(map_str);
+        let _map: Map = Map::from_map_str(map_str);
     }
 }
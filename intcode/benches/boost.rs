@@ -0,0 +1,27 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use intcode::{IntCodeComputer, Program};
+
+// Day 9 part 2: BOOST in sensor boost mode, which runs for a few hundred
+// thousand instructions, mostly in a recursive routine.
+fn boost(c: &mut Criterion) {
+    let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/../day09/input");
+    let program = Program::from_file(filename).unwrap();
+
+    let mut group = c.benchmark_group("day09 sensor boost");
+    for &(name, fuse) in [("unfused", false), ("fused", true)].iter() {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut computer = IntCodeComputer::new(&program);
+                computer.set_fusion(fuse);
+                #[cfg(feature = "jit")]
+                computer.set_jit(false);
+                computer.run_to_halt(&[2]).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, boost);
+criterion_main!(benches);
@@ -0,0 +1,282 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+#[cfg(feature = "bigint")]
+use num_bigint::{BigInt, Sign};
+
+// What `add` and `mul` do with a result that doesn't fit in an i64.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Arithmetic {
+    // Fail with `IntcodeError::Overflow`.
+    #[default]
+    Checked,
+    // Wrap around, as two's complement does.
+    Wrapping,
+    // Keep results of up to 128 bits, failing beyond that.
+    I128,
+    // Keep results of any size.
+    #[cfg(feature = "bigint")]
+    BigInt,
+}
+
+// A value kept by a computer using `Arithmetic::I128` or `BigInt`, which
+// may not fit in an i64.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Wide(Repr);
+
+// Anything that fits in an i128 is `Small`, so equal values are equal
+// representations.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Repr {
+    Small(i128),
+    #[cfg(feature = "bigint")]
+    Big(BigInt),
+}
+
+impl Wide {
+    pub fn to_i64(&self) -> Option<i64> {
+        self.to_i128().and_then(|value| i64::try_from(value).ok())
+    }
+
+    pub fn to_i128(&self) -> Option<i128> {
+        match self.0 {
+            Repr::Small(value) => Some(value),
+            #[cfg(feature = "bigint")]
+            Repr::Big(_) => None,
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    pub fn to_bigint(&self) -> BigInt {
+        match &self.0 {
+            Repr::Small(value) => BigInt::from(*value),
+            Repr::Big(value) => value.clone(),
+        }
+    }
+
+    fn is_negative(&self) -> bool {
+        match &self.0 {
+            Repr::Small(value) => *value < 0,
+            #[cfg(feature = "bigint")]
+            Repr::Big(value) => value.sign() == Sign::Minus,
+        }
+    }
+
+    // The nearest i64, which memory holds in place of a wider value.
+    pub(crate) fn saturate(&self) -> i64 {
+        match self.to_i64() {
+            Some(value) => value,
+            None if self.is_negative() => i64::MIN,
+            None => i64::MAX,
+        }
+    }
+
+    // `self + other`, or `None` if that's out of the range `arithmetic`
+    // allows. Wrapping arithmetic never gets here.
+    pub(crate) fn checked_add(&self, other: &Wide, arithmetic: Arithmetic) -> Option<Wide> {
+        let result = match (&self.0, &other.0) {
+            (Repr::Small(a), Repr::Small(b)) => a.checked_add(*b).map(Wide::from),
+            #[cfg(feature = "bigint")]
+            _ => None,
+        };
+        #[cfg(feature = "bigint")]
+        let result = result.or_else(|| big(arithmetic, || self.to_bigint() + other.to_bigint()));
+        result.filter(|value| value.fits(arithmetic))
+    }
+
+    // `self * other`, like `checked_add`.
+    pub(crate) fn checked_mul(&self, other: &Wide, arithmetic: Arithmetic) -> Option<Wide> {
+        let result = match (&self.0, &other.0) {
+            (Repr::Small(a), Repr::Small(b)) => a.checked_mul(*b).map(Wide::from),
+            #[cfg(feature = "bigint")]
+            _ => None,
+        };
+        #[cfg(feature = "bigint")]
+        let result = result.or_else(|| big(arithmetic, || self.to_bigint() * other.to_bigint()));
+        result.filter(|value| value.fits(arithmetic))
+    }
+
+    fn fits(&self, arithmetic: Arithmetic) -> bool {
+        match arithmetic {
+            Arithmetic::Checked | Arithmetic::Wrapping => self.to_i64().is_some(),
+            Arithmetic::I128 => self.to_i128().is_some(),
+            #[cfg(feature = "bigint")]
+            Arithmetic::BigInt => true,
+        }
+    }
+}
+
+// Redoes an operation that overflowed an i128 with arbitrary precision, if
+// `arithmetic` allows that.
+#[cfg(feature = "bigint")]
+fn big<F: FnOnce() -> BigInt>(arithmetic: Arithmetic, f: F) -> Option<Wide> {
+    if arithmetic == Arithmetic::BigInt {
+        Some(Wide::from(f()))
+    } else {
+        None
+    }
+}
+
+impl From<i64> for Wide {
+    fn from(value: i64) -> Wide {
+        Wide(Repr::Small(value.into()))
+    }
+}
+
+impl From<i128> for Wide {
+    fn from(value: i128) -> Wide {
+        Wide(Repr::Small(value))
+    }
+}
+
+#[cfg(feature = "bigint")]
+impl From<BigInt> for Wide {
+    fn from(value: BigInt) -> Wide {
+        match i128::try_from(&value) {
+            Ok(value) => Wide(Repr::Small(value)),
+            Err(_) => Wide(Repr::Big(value)),
+        }
+    }
+}
+
+impl Ord for Wide {
+    fn cmp(&self, other: &Wide) -> Ordering {
+        match (&self.0, &other.0) {
+            (Repr::Small(a), Repr::Small(b)) => a.cmp(b),
+            #[cfg(feature = "bigint")]
+            _ => self.to_bigint().cmp(&other.to_bigint()),
+        }
+    }
+}
+
+impl PartialOrd for Wide {
+    fn partial_cmp(&self, other: &Wide) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Wide {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            Repr::Small(value) => write!(f, "{}", value),
+            #[cfg(feature = "bigint")]
+            Repr::Big(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::computer::{IntCodeComputer, RunState};
+    use crate::error::IntcodeError;
+
+    // Squares its input twice and prints it, then whether it's more than
+    // the largest i64, then jumps to it.
+    const SOURCE: &str = "
+                in @x
+                mul @x, @x, @x
+                mul @x, @x, @x
+                out @x
+                lt #9223372036854775807, @x, @t
+                out @t
+                jnz #1, @x
+        x:      data 0
+        t:      data 0
+    ";
+
+    fn computer(arithmetic: Arithmetic, input: i64) -> IntCodeComputer {
+        let mut computer = IntCodeComputer::from_program(&assemble(SOURCE).unwrap());
+        computer.set_arithmetic(arithmetic);
+        computer.push_input(input);
+        computer
+    }
+
+    #[test]
+    fn test() {
+        let overflow = IntcodeError::Overflow {
+            pc: 6,
+            instruction: 2,
+        };
+        let mut checked = computer(Arithmetic::Checked, 10);
+        assert!(checked.run().unwrap() == RunState::Output(10000));
+        let mut checked = computer(Arithmetic::Checked, 100_000);
+        assert!(checked.run().unwrap_err() == overflow);
+        assert!(checked.read(21) == 10_000_000_000);
+
+        let mut wrapping = computer(Arithmetic::Wrapping, 100_000);
+        let wrapped = 100_000_000_000_000_000_000u128 as i64;
+        assert!(wrapping.run().unwrap() == RunState::Output(wrapped));
+
+        // Only `run_wide` can print a wide value.
+        let mut i128 = computer(Arithmetic::I128, 100_000);
+        let overflow = IntcodeError::Overflow {
+            pc: 10,
+            instruction: 4,
+        };
+        assert!(i128.run().unwrap_err() == overflow);
+        let value = Wide::from(100_000_000_000_000_000_000i128);
+        assert!(i128.run_wide().unwrap() == RunState::Output(value.clone()));
+        assert!(i128.read(21) == i64::MAX && i128.read_wide(21) == value);
+        assert!(i128.run_wide().unwrap() == RunState::Output(Wide::from(1i64)));
+        let overflow = IntcodeError::Overflow {
+            pc: 18,
+            instruction: 105,
+        };
+        assert!(i128.run().unwrap_err() == overflow);
+        // Writing over a wide value forgets it.
+        i128.write(21, 5);
+        assert!(i128.read_wide(21) == Wide::from(5i64));
+
+        let mut i128 = computer(Arithmetic::I128, 10_000_000_000);
+        assert!(i128.run().unwrap_err().pc() == 6);
+
+        // Squares 2^32 into the parameter of the instruction after it.
+        let run = |program: &str| {
+            let mut computer = IntCodeComputer::from_program(program);
+            computer.set_arithmetic(Arithmetic::I128);
+            computer.run_to_halt_wide(&[])
+        };
+        let squared = vec![Wide::from(1i128 << 64)];
+        assert!(run("2,9,9,5,104,0,99,0,0,4294967296") == Ok(squared.clone()));
+        assert!(run("1102,4294967296,4294967296,7,4,7,99,0") == Ok(squared));
+        let overflow = IntcodeError::Overflow {
+            pc: 4,
+            instruction: 4,
+        };
+        assert!(run("2,9,9,5,4,0,99,0,0,4294967296") == Err(overflow));
+        let overflow = IntcodeError::Overflow {
+            pc: 4,
+            instruction: 9,
+        };
+        assert!(run("2,7,7,7,9,7,99,4294967296") == Err(overflow));
+        let mut computer = IntCodeComputer::from_program("2,9,9,5,104,0,99,0,0,4294967296");
+        computer.set_arithmetic(Arithmetic::I128);
+        assert!(computer.run_to_halt(&[]).unwrap_err().pc() == 4);
+        assert!(computer.run_to_halt_wide(&[]).unwrap() == vec![Wide::from(1i128 << 64)]);
+
+        let mut computer = IntCodeComputer::from_program("109,1,109,9223372036854775807,99");
+        let overflow = IntcodeError::Overflow {
+            pc: 2,
+            instruction: 109,
+        };
+        assert!(computer.run().unwrap_err() == overflow);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn bigint() {
+        let mut computer = computer(Arithmetic::BigInt, 10_000_000_000);
+        let value = BigInt::from(10u32).pow(40);
+        let output = computer.run_wide().unwrap();
+        assert!(output == RunState::Output(Wide::from(value.clone())));
+        assert!(computer.run().unwrap() == RunState::Output(1));
+
+        let big = Wide::from(value.clone());
+        assert!(big > Wide::from(i128::MAX) && -value < BigInt::from(i128::MIN));
+        assert!(big.to_string() == format!("1{}", "0".repeat(40)));
+        assert!(Wide::from(BigInt::from(-3)) == Wide::from(-3i64));
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+
+use crate::instruction::{Instruction, Mode, Opcode};
+
+// Assembles Intcode from text into the comma-separated form read by
+// `Program::parse`. Each line holds optional labels, then an instruction or
+// directive, then an optional `;` comment:
+//
+//     loop:  in @x             ; position mode operand
+//            add @x, #-1, @x   ; `#` marks an immediate
+//            out rb+2          ; relative to the relative base
+//            jnz @x, #loop     ; labels evaluate to their address
+//            hlt
+//     x:     data 0, 1, end+2
+//     msg:   string "hi\n"
+//     end:
+//
+// Bare operands are position mode, so `@x` and `x` are the same thing.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl error::Error for AsmError {}
+
+pub fn assemble(source: &str) -> Result<String, AsmError> {
+    let words = assemble_words(source)?;
+    Ok(words
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<String>>()
+        .join(","))
+}
+
+pub fn assemble_words(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut words: Vec<Value> = Vec::new();
+    let mut labels: HashMap<String, i64> = HashMap::new();
+
+    for (index, text) in source.lines().enumerate() {
+        let mut line = Line {
+            text,
+            number: index + 1,
+            pos: 0,
+        };
+        line.parse(&mut words, &mut labels)?;
+    }
+
+    words
+        .iter()
+        .map(|value| match &value.label {
+            None => Ok(value.offset),
+            Some(label) => match labels.get(label) {
+                Some(address) => match address.checked_add(value.offset) {
+                    Some(address) => Ok(address),
+                    None => Err(AsmError {
+                        line: value.line,
+                        column: value.column,
+                        message: format!("`{}` plus {} is out of range", label, value.offset),
+                    }),
+                },
+                None => Err(AsmError {
+                    line: value.line,
+                    column: value.column,
+                    message: format!("undefined label `{}`", label),
+                }),
+            },
+        })
+        .collect()
+}
+
+// A word of output: a number, or a label plus an offset to be resolved once
+// every label's address is known.
+#[derive(Debug)]
+struct Value {
+    label: Option<String>,
+    offset: i64,
+    line: usize,
+    column: usize,
+}
+
+struct Line<'a> {
+    text: &'a str,
+    number: usize,
+    pos: usize,
+}
+
+impl<'a> Line<'a> {
+    fn parse(
+        &mut self,
+        words: &mut Vec<Value>,
+        labels: &mut HashMap<String, i64>,
+    ) -> Result<(), AsmError> {
+        loop {
+            self.skip_whitespace();
+            if self.at_end() {
+                return Ok(());
+            }
+            let start = self.pos;
+            let name = match self.identifier() {
+                Some(name) => name,
+                None => return Err(self.error(start, "expected a label or mnemonic")),
+            };
+            self.skip_whitespace();
+            if !self.eat(':') {
+                self.parse_statement(name, start, words)?;
+                break;
+            }
+            if name == "rb" {
+                return Err(self.error(start, "`rb` is reserved and can't be a label"));
+            }
+            if labels
+                .insert(name.to_string(), words.len() as i64)
+                .is_some()
+            {
+                return Err(self.error(start, &format!("duplicate label `{}`", name)));
+            }
+        }
+        self.skip_whitespace();
+        if !self.at_end() {
+            return Err(self.error(self.pos, "unexpected trailing characters"));
+        }
+        Ok(())
+    }
+
+    fn parse_statement(
+        &mut self,
+        name: &str,
+        start: usize,
+        words: &mut Vec<Value>,
+    ) -> Result<(), AsmError> {
+        match name {
+            "data" => {
+                words.push(self.term()?);
+                while self.eat(',') {
+                    words.push(self.term()?);
+                }
+            }
+            "string" => {
+                for c in self.string()?.chars() {
+                    words.push(self.number_value(c as i64, start));
+                }
+            }
+            _ => {
+                let opcode = match Opcode::from_mnemonic(name) {
+                    Some(opcode) => opcode,
+                    None => return Err(self.error(start, &format!("unknown mnemonic `{}`", name))),
+                };
+                let mut operands: Vec<(Mode, Value, usize)> = Vec::new();
+                if !self.at_end() {
+                    operands.push(self.operand()?);
+                    while self.eat(',') {
+                        operands.push(self.operand()?);
+                    }
+                }
+                if operands.len() != opcode.num_params() {
+                    return Err(self.error(
+                        start,
+                        &format!(
+                            "`{}` takes {} operands, found {}",
+                            name,
+                            opcode.num_params(),
+                            operands.len()
+                        ),
+                    ));
+                }
+                let mut modes = [Mode::Position; 3];
+                for (i, (mode, _, column)) in operands.iter().enumerate() {
+                    if opcode.writes() && i == operands.len() - 1 && *mode == Mode::Immediate {
+                        return Err(self.error(*column, "write target can't be immediate"));
+                    }
+                    modes[i] = *mode;
+                }
+                let instruction = Instruction { opcode, modes };
+                words.push(self.number_value(instruction.encode(), start));
+                words.extend(operands.into_iter().map(|(_, value, _)| value));
+            }
+        }
+        Ok(())
+    }
+
+    fn operand(&mut self) -> Result<(Mode, Value, usize), AsmError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        if self.eat('#') {
+            return Ok((Mode::Immediate, self.term()?, start));
+        }
+        if self.eat('@') {
+            return Ok((Mode::Position, self.term()?, start));
+        }
+        let rest = &self.text[self.pos..];
+        if rest.starts_with("rb") && !rest[2..].starts_with(is_identifier_char) {
+            self.pos += 2;
+            self.skip_whitespace();
+            let value = match self.peek() {
+                Some('+') | Some('-') => self.term()?,
+                _ => self.number_value(0, self.pos),
+            };
+            return Ok((Mode::Relative, value, start));
+        }
+        Ok((Mode::Position, self.term()?, start))
+    }
+
+    // A number, or a label optionally followed by `+n` or `-n`.
+    fn term(&mut self) -> Result<Value, AsmError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        if let Some(label) = self.identifier() {
+            self.skip_whitespace();
+            let offset = match self.peek() {
+                Some('+') | Some('-') => self.number()?,
+                _ => 0,
+            };
+            return Ok(Value {
+                label: Some(label.to_string()),
+                offset,
+                line: self.number,
+                column: self.column(start),
+            });
+        }
+        let number = self.number()?;
+        Ok(self.number_value(number, start))
+    }
+
+    fn number(&mut self) -> Result<i64, AsmError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let mut negative = false;
+        if self.eat('-') {
+            negative = true;
+        } else {
+            self.eat('+');
+        }
+        self.skip_whitespace();
+        let digits_start = self.pos;
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            self.pos += 1;
+        }
+        let digits = &self.text[digits_start..self.pos];
+        if digits.is_empty() {
+            return Err(self.error(start, "expected a number or label"));
+        }
+        // The sign goes with the digits, since i64::MIN has no positive
+        // counterpart.
+        let parsed = if negative {
+            format!("-{}", digits).parse()
+        } else {
+            digits.parse()
+        };
+        match parsed {
+            Ok(value) => Ok(value),
+            Err(_) => Err(self.error(start, "number out of range")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, AsmError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        if !self.eat('"') {
+            return Err(self.error(start, "expected a string literal"));
+        }
+        let mut string = String::new();
+        loop {
+            let c = match self.next() {
+                Some(c) => c,
+                None => return Err(self.error(start, "unterminated string literal")),
+            };
+            match c {
+                '"' => return Ok(string),
+                '\\' => {
+                    let escape_start = self.pos - 1;
+                    match self.next() {
+                        Some('n') => string.push('\n'),
+                        Some('t') => string.push('\t'),
+                        Some('\\') => string.push('\\'),
+                        Some('"') => string.push('"'),
+                        _ => return Err(self.error(escape_start, "unknown escape sequence")),
+                    }
+                }
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn identifier(&mut self) -> Option<&'a str> {
+        let rest = &self.text[self.pos..];
+        if !rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            return None;
+        }
+        let length = rest.find(|c| !is_identifier_char(c)).unwrap_or(rest.len());
+        self.pos += length;
+        Some(&rest[..length])
+    }
+
+    fn number_value(&self, value: i64, start: usize) -> Value {
+        Value {
+            label: None,
+            offset: value,
+            line: self.number,
+            column: self.column(start),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+        if self.peek() == Some(';') {
+            self.pos = self.text.len();
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            return true;
+        }
+        false
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.text.len()
+    }
+
+    fn column(&self, pos: usize) -> usize {
+        self.text[..pos].chars().count() + 1
+    }
+
+    fn error(&self, pos: usize, message: &str) -> AsmError {
+        AsmError {
+            line: self.number,
+            column: self.column(pos),
+            message: message.to_string(),
+        }
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::computer::IntCodeComputer;
+
+    #[test]
+    fn test() {
+        assert!(assemble("add @9, #3, 0\nhlt").unwrap() == "1001,9,3,0,99");
+        assert!(
+            assemble("arb #1\nout rb-1\nin rb\neq rb + 2, #8, @3").unwrap()
+                == "109,1,204,-1,203,0,1208,2,8,3"
+        );
+        assert!(assemble("data 1, -2, +3\nstring \"a\\n\"").unwrap() == "1,-2,3,97,10");
+        assert!(assemble("data -9223372036854775808").unwrap() == "-9223372036854775808");
+
+        let source = "
+            ; counts down from the input to zero
+                    in @x
+            loop:   out @x
+                    add @x, #-1, @x
+                    lt @x, #0, @done
+                    jz @done, #loop   ; labels work as immediates
+                    hlt
+            x:      data 0
+            done:   data 0
+        ";
+        let program = assemble(source).unwrap();
+        let mut computer = IntCodeComputer::from_program(&program);
+        assert!(computer.run_to_halt(&[3]).unwrap() == vec![3, 2, 1, 0]);
+
+        let source = "
+                    out msg+1
+                    out #end-1
+                    hlt
+            msg:    string \"hi\"
+            end:
+        ";
+        let program = assemble(source).unwrap();
+        let mut computer = IntCodeComputer::from_program(&program);
+        assert!(computer.run_to_halt(&[]).unwrap() == vec![105, 6]);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |source: &str| {
+            let error = assemble(source).unwrap_err();
+            (error.line, error.column, error.message)
+        };
+        assert!(error("hlt\n  foo @1") == (2, 3, "unknown mnemonic `foo`".to_string()));
+        assert!(error("add #1, #2") == (1, 1, "`add` takes 3 operands, found 2".to_string()));
+        assert!(error("add #1, #2, #3") == (1, 13, "write target can't be immediate".to_string()));
+        assert!(error("out @nowhere") == (1, 6, "undefined label `nowhere`".to_string()));
+        assert!(error("x: hlt\nx: hlt") == (2, 1, "duplicate label `x`".to_string()));
+        assert!(error("out #") == (1, 6, "expected a number or label".to_string()));
+        assert!(error("out #1 #2") == (1, 8, "unexpected trailing characters".to_string()));
+        assert!(error("string \"abc") == (1, 8, "unterminated string literal".to_string()));
+        assert!(error("rb: hlt") == (1, 1, "`rb` is reserved and can't be a label".to_string()));
+        assert!(error("1, 2") == (1, 1, "expected a label or mnemonic".to_string()));
+        assert!(error("data -9223372036854775809") == (1, 6, "number out of range".to_string()));
+        assert!(
+            error("data 0\nx: data x+9223372036854775807")
+                == (
+                    2,
+                    9,
+                    "`x` plus 9223372036854775807 is out of range".to_string()
+                )
+        );
+    }
+}
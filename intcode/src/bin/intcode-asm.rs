@@ -0,0 +1,22 @@
+use std::env;
+use std::error;
+use std::fs;
+
+use intcode::assemble;
+
+type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
+
+fn main() -> Result<()> {
+    let filename = match env::args().nth(1) {
+        Some(filename) => filename,
+        None => return Err("usage: intcode-asm <source>".into()),
+    };
+    let source = fs::read_to_string(&filename)?;
+
+    match assemble(&source) {
+        Ok(program) => println!("{}", program),
+        Err(error) => return Err(format!("{}: {}", filename, error).into()),
+    }
+
+    Ok(())
+}
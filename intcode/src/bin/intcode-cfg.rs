@@ -0,0 +1,20 @@
+use std::env;
+use std::error;
+
+use intcode::{recover_cfg, Program};
+
+type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
+
+// Prints the control-flow graph of a program as Graphviz DOT, e.g.
+// `intcode-cfg day17/input | dot -Tsvg > cfg.svg`.
+fn main() -> Result<()> {
+    let filename = match env::args().nth(1) {
+        Some(filename) => filename,
+        None => return Err("usage: intcode-cfg <program>".into()),
+    };
+    let program = Program::from_file(filename)?;
+
+    println!("{}", recover_cfg(program.words()).to_dot());
+
+    Ok(())
+}
@@ -0,0 +1,400 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::env;
+use std::error;
+use std::io::{self, BufRead, Write};
+
+use intcode::{format_instruction, Instruction, IntCodeComputer, Program, Stop};
+
+type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
+
+// How many instructions back the debugger can always go.
+const HISTORY: u64 = 1_000_000;
+
+const HELP: &str = "\
+step [N]          execute N instructions (default 1)
+continue          run until a breakpoint, watchpoint, input request or halt
+back [N]          undo the last N instructions (default 1)
+lastwrite ADDR    go back to just before the last write to ADDR
+break [ADDR]      set a breakpoint at ADDR, or list breakpoints
+delete ADDR       remove the breakpoint at ADDR
+watch [ADDR]      stop when the value at ADDR changes, or list watchpoints
+unwatch ADDR      remove the watchpoint at ADDR
+print ADDR [END]  show memory from ADDR up to and including END
+regs              show pc, relative base and the current instruction
+disas [ADDR] [N]  disassemble N instructions starting at ADDR (default pc)
+input V...        queue input values
+ascii TEXT        queue TEXT followed by a newline as ASCII input
+output            show and clear output printed so far
+quit              exit the debugger";
+
+struct Debugger {
+    computer: IntCodeComputer,
+    breakpoints: BTreeSet<i64>,
+    // Watched addresses and the value they held after the last step.
+    watchpoints: BTreeMap<i64, i64>,
+    output: Vec<i64>,
+}
+
+impl Debugger {
+    fn new(program: &Program) -> Debugger {
+        let mut computer = IntCodeComputer::new(program);
+        computer.enable_history(HISTORY);
+        Debugger {
+            computer,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            output: Vec::new(),
+        }
+    }
+
+    // Runs one command and returns the text to show for it.
+    fn execute(&mut self, line: &str) -> String {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return String::new(),
+        };
+        let result = match command {
+            "step" | "s" => self.step(args),
+            "continue" | "c" => self.continue_(),
+            "back" => self.back(args),
+            "lastwrite" => self.last_write(args),
+            "break" | "b" => self.break_(args),
+            "delete" | "d" => self.delete(args),
+            "watch" | "w" => self.watch(args),
+            "unwatch" => self.unwatch(args),
+            "print" | "p" => self.print(args),
+            "regs" | "r" => Ok(self.regs()),
+            "disas" => self.disas(args),
+            "input" | "i" => self.input(args),
+            "ascii" => Ok(self.ascii(line)),
+            "output" | "o" => Ok(self.take_output()),
+            "help" | "h" => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command `{}`, try `help`", command).into()),
+        };
+        match result {
+            Ok(text) => text,
+            Err(error) => format!("error: {}", error),
+        }
+    }
+
+    fn step(&mut self, args: &[&str]) -> Result<String> {
+        let count = match args.first() {
+            Some(count) => count.parse()?,
+            None => 1,
+        };
+        for _ in 0..count {
+            if let Some(stop) = self.single_step()? {
+                return Ok(stop);
+            }
+        }
+        Ok(self.regs())
+    }
+
+    fn continue_(&mut self) -> Result<String> {
+        // Always make progress, even when sitting on a breakpoint.
+        if let Some(stop) = self.single_step()? {
+            return Ok(stop);
+        }
+        loop {
+            if self.breakpoints.contains(&self.computer.pc()) {
+                return Ok(format!(
+                    "breakpoint at {}\n{}",
+                    self.computer.pc(),
+                    self.regs()
+                ));
+            }
+            if let Some(stop) = self.single_step()? {
+                return Ok(stop);
+            }
+        }
+    }
+
+    fn back(&mut self, args: &[&str]) -> Result<String> {
+        let count = match args.first() {
+            Some(count) => count.parse()?,
+            None => 1,
+        };
+        let undone = self.computer.step_back(count)?;
+        self.update_watchpoints();
+        if undone < count {
+            return Ok(format!(
+                "went back {} instructions\n{}",
+                undone,
+                self.regs()
+            ));
+        }
+        Ok(self.regs())
+    }
+
+    fn last_write(&mut self, args: &[&str]) -> Result<String> {
+        let address = parse_address(args)?;
+        let found = self.computer.step_back_to_write(address)?;
+        self.update_watchpoints();
+        if !found {
+            return Ok(format!(
+                "no write to {} in history\n{}",
+                address,
+                self.regs()
+            ));
+        }
+        Ok(self.regs())
+    }
+
+    // Watchpoints compare against the values after the last step, which
+    // going back changes.
+    fn update_watchpoints(&mut self) {
+        for (address, value) in self.watchpoints.iter_mut() {
+            *value = self.computer.read(*address);
+        }
+    }
+
+    // Executes one instruction, returning a description of why execution
+    // should stop if it should.
+    fn single_step(&mut self) -> Result<Option<String>> {
+        let pc = self.computer.pc();
+        let status = self.computer.step()?;
+        self.output.extend(&status.output);
+        let mut changes = Vec::new();
+        for (address, old) in self.watchpoints.iter_mut() {
+            let new = self.computer.read(*address);
+            if new != *old {
+                changes.push(format!(
+                    "watchpoint {}: {} -> {} (written by pc {})",
+                    address, old, new, pc
+                ));
+                *old = new;
+            }
+        }
+        let stop = match status.stop {
+            Stop::Halted => Some("halted".to_string()),
+            Stop::NeedsInput => Some(format!("waiting for input at pc {}", pc)),
+            _ if !changes.is_empty() => Some(format!("{}\n{}", changes.join("\n"), self.regs())),
+            _ => None,
+        };
+        Ok(stop)
+    }
+
+    fn break_(&mut self, args: &[&str]) -> Result<String> {
+        match args.first() {
+            Some(address) => {
+                let address = address.parse()?;
+                self.breakpoints.insert(address);
+                Ok(format!("breakpoint at {}", address))
+            }
+            None => Ok(list(self.breakpoints.iter())),
+        }
+    }
+
+    fn delete(&mut self, args: &[&str]) -> Result<String> {
+        let address = parse_address(args)?;
+        if !self.breakpoints.remove(&address) {
+            return Err(format!("no breakpoint at {}", address).into());
+        }
+        Ok(format!("deleted breakpoint at {}", address))
+    }
+
+    fn watch(&mut self, args: &[&str]) -> Result<String> {
+        match args.first() {
+            Some(address) => {
+                let address = address.parse()?;
+                let value = self.computer.read(address);
+                self.watchpoints.insert(address, value);
+                Ok(format!("watching {} (currently {})", address, value))
+            }
+            None => Ok(list(self.watchpoints.keys())),
+        }
+    }
+
+    fn unwatch(&mut self, args: &[&str]) -> Result<String> {
+        let address = parse_address(args)?;
+        if self.watchpoints.remove(&address).is_none() {
+            return Err(format!("no watchpoint at {}", address).into());
+        }
+        Ok(format!("removed watchpoint at {}", address))
+    }
+
+    fn print(&mut self, args: &[&str]) -> Result<String> {
+        let start = parse_address(args)?;
+        let end = match args.get(1) {
+            Some(end) => end.parse()?,
+            None => start,
+        };
+        let lines: Vec<String> = (start..=end)
+            .map(|address| format!("{:>6}  {}", address, self.computer.read(address)))
+            .collect();
+        Ok(lines.join("\n"))
+    }
+
+    fn regs(&self) -> String {
+        let pc = self.computer.pc();
+        let mut text = format!(
+            "pc {}  rb {}  input queued {}",
+            pc,
+            self.computer.relative_base(),
+            self.computer.input_len()
+        );
+        if self.computer.is_halted() {
+            text.push_str("  halted");
+        }
+        text.push('\n');
+        text.push_str(&self.format_at(pc).1);
+        text
+    }
+
+    fn disas(&mut self, args: &[&str]) -> Result<String> {
+        let mut address = match args.first() {
+            Some(address) => address.parse()?,
+            None => self.computer.pc(),
+        };
+        let count = match args.get(1) {
+            Some(count) => count.parse()?,
+            None => 10,
+        };
+        let mut lines = Vec::new();
+        for _ in 0..count {
+            let (length, line) = self.format_at(address);
+            lines.push(line);
+            address += length;
+        }
+        Ok(lines.join("\n"))
+    }
+
+    // Disassembles the instruction at `address`, returning its length and
+    // the formatted line. Words that don't decode are shown as data.
+    fn format_at(&self, address: i64) -> (i64, String) {
+        let marker = if address == self.computer.pc() {
+            "=>"
+        } else {
+            "  "
+        };
+        let word = self.computer.read(address);
+        match Instruction::decode(address, word) {
+            Ok(instruction) => {
+                let params: Vec<i64> = (1..instruction.length())
+                    .map(|offset| self.computer.read(address + offset))
+                    .collect();
+                (
+                    instruction.length(),
+                    format!(
+                        "{} {:>6}  {}",
+                        marker,
+                        address,
+                        format_instruction(&instruction, &params)
+                    ),
+                )
+            }
+            Err(_) => (1, format!("{} {:>6}  data {}", marker, address, word)),
+        }
+    }
+
+    fn input(&mut self, args: &[&str]) -> Result<String> {
+        if args.is_empty() {
+            return Err("usage: input V...".into());
+        }
+        for value in args {
+            let value = value.parse()?;
+            self.computer.push_input(value);
+        }
+        Ok(format!("queued {} values", args.len()))
+    }
+
+    fn ascii(&mut self, line: &str) -> String {
+        let text = line.trim_start();
+        let text = text[text.find(char::is_whitespace).unwrap_or(text.len())..].trim_start();
+        for c in text.chars().chain("\n".chars()) {
+            self.computer.push_input(c as i64);
+        }
+        format!("queued {} values", text.chars().count() + 1)
+    }
+
+    fn take_output(&mut self) -> String {
+        let output: Vec<String> = self.output.drain(..).map(|x| x.to_string()).collect();
+        output.join(",")
+    }
+}
+
+fn parse_address(args: &[&str]) -> Result<i64> {
+    match args.first() {
+        Some(address) => Ok(address.parse()?),
+        None => Err("expected an address".into()),
+    }
+}
+
+fn list<'a>(addresses: impl Iterator<Item = &'a i64>) -> String {
+    let addresses: Vec<String> = addresses.map(|x| x.to_string()).collect();
+    addresses.join(" ")
+}
+
+fn main() -> Result<()> {
+    let filename = match env::args().nth(1) {
+        Some(filename) => filename,
+        None => return Err("usage: intcode-dbg <program>".into()),
+    };
+    let program = Program::from_file(filename)?;
+    let mut debugger = Debugger::new(&program);
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("(icdb) ");
+        io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        let line = line.trim();
+        if line == "quit" || line == "q" {
+            break;
+        }
+        let text = debugger.execute(line);
+        if !text.is_empty() {
+            println!("{}", text);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        // Sums its input into address 12 until it reads a zero, then prints
+        // the total.
+        let program = Program::parse("3,13,1,12,13,12,1005,13,0,4,12,99,0,0");
+        let mut debugger = Debugger::new(&program);
+
+        assert!(debugger.execute("regs") == "pc 0  rb 0  input queued 0\n=>      0  in @13");
+        assert!(debugger.execute("c") == "waiting for input at pc 0");
+        assert!(debugger.execute("watch 12") == "watching 12 (currently 0)");
+        assert!(debugger.execute("break 6") == "breakpoint at 6");
+        assert!(debugger.execute("input 5 0") == "queued 2 values");
+        assert!(debugger
+            .execute("c")
+            .starts_with("watchpoint 12: 0 -> 5 (written by pc 2)"));
+        assert!(debugger.execute("c").starts_with("breakpoint at 6"));
+        assert!(debugger.execute("s") == "pc 9  rb 0  input queued 0\n=>      9  out @12");
+        debugger.execute("delete 6");
+        assert!(debugger.execute("c") == "halted");
+        assert!(debugger.execute("output") == "5");
+        assert!(debugger.execute("p 12 13") == "    12  5\n    13  0");
+        assert!(
+            debugger.execute("lastwrite 12")
+                == "pc 2  rb 0  input queued 0\n=>      2  add @12, @13, @12"
+        );
+        assert!(debugger.execute("back 2") == "pc 6  rb 0  input queued 1\n=>      6  jnz @13, #0");
+        assert!(debugger
+            .execute("back 10")
+            .starts_with("went back 2 instructions\npc 0"));
+        assert!(debugger
+            .execute("lastwrite 12")
+            .starts_with("no write to 12 in history"));
+        assert!(debugger
+            .execute("frobnicate")
+            .starts_with("error: unknown command"));
+    }
+}
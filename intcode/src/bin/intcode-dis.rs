@@ -0,0 +1,18 @@
+use std::env;
+use std::error;
+
+use intcode::{disassemble, Program};
+
+type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
+
+fn main() -> Result<()> {
+    let filename = match env::args().nth(1) {
+        Some(filename) => filename,
+        None => return Err("usage: intcode-dis <program>".into()),
+    };
+    let program = Program::from_file(filename)?;
+
+    println!("{}", disassemble(program.words()));
+
+    Ok(())
+}
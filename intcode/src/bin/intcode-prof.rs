@@ -0,0 +1,46 @@
+use std::env;
+use std::error;
+use std::fs;
+
+use intcode::{IntCodeComputer, Program};
+
+type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
+
+const USAGE: &str = "usage: intcode-prof [--folded <file>] <program> [input...]";
+
+// Runs a program with the given input values and prints a profile of where
+// it spent its time. With `--folded`, also writes folded stacks for
+// flamegraph tools to the given file.
+fn main() -> Result<()> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let mut folded = None;
+    if args.first().map(|x| x.as_str()) == Some("--folded") {
+        if args.len() < 2 {
+            return Err(USAGE.into());
+        }
+        folded = Some(args.remove(1));
+        args.remove(0);
+    }
+    if args.is_empty() {
+        return Err(USAGE.into());
+    }
+    let program = Program::from_file(&args[0])?;
+    let input: Vec<i64> = args[1..]
+        .iter()
+        .map(|x| x.parse())
+        .collect::<std::result::Result<_, _>>()?;
+
+    let mut computer = IntCodeComputer::new(&program);
+    computer.enable_profiling();
+    let output = computer.run_to_halt(&input)?;
+    let profile = computer.profile().unwrap();
+
+    println!("output: {:?}", output);
+    println!("self-modifying writes: {}\n", computer.stats().code_writes);
+    println!("{}", profile.report(20));
+    if let Some(filename) = folded {
+        fs::write(filename, profile.folded_stacks() + "\n")?;
+    }
+
+    Ok(())
+}
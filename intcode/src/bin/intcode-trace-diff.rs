@@ -0,0 +1,41 @@
+use std::env;
+use std::error;
+use std::fs;
+use std::process;
+
+use intcode::{diff_traces, TraceRecord};
+
+type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
+
+fn read_trace(filename: &str) -> Result<Vec<TraceRecord>> {
+    let contents = fs::read_to_string(filename)?;
+    let mut records = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        match TraceRecord::from_json(line) {
+            Ok(record) => records.push(record),
+            Err(error) => return Err(format!("{}:{}: {}", filename, index + 1, error).into()),
+        }
+    }
+    Ok(records)
+}
+
+// Compares two traces written by `intcode-trace`, reporting the first
+// record where they differ. Exits with status 1 if they do.
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        return Err("usage: intcode-trace-diff <left> <right>".into());
+    }
+    let left = read_trace(&args[1])?;
+    let right = read_trace(&args[2])?;
+
+    match diff_traces(left.clone(), right) {
+        Some(divergence) => {
+            println!("{}", divergence);
+            process::exit(1);
+        }
+        None => println!("traces match ({} records)", left.len()),
+    }
+
+    Ok(())
+}
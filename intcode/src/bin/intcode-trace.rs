@@ -0,0 +1,29 @@
+use std::env;
+use std::error;
+use std::io;
+
+use intcode::{IntCodeComputer, Program};
+
+type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
+
+// Runs a program with the given input values, writing its execution trace
+// to stdout as JSON lines.
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        return Err("usage: intcode-trace <program> [input...]".into());
+    }
+    let program = Program::from_file(&args[1])?;
+    let input: Vec<i64> = args[2..]
+        .iter()
+        .map(|x| x.parse())
+        .collect::<std::result::Result<_, _>>()?;
+
+    let mut computer = IntCodeComputer::new(&program);
+    computer.trace_to(io::BufWriter::new(io::stdout()));
+    let result = computer.run_to_halt(&input);
+    computer.finish_trace()?;
+    result?;
+
+    Ok(())
+}
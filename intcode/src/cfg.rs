@@ -0,0 +1,396 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::disasm::format_instruction;
+use crate::instruction::{Instruction, Mode, Opcode};
+
+// A control-flow graph recovered from a program image by following jumps
+// from address 0. Only jumps with immediate targets can be followed; the
+// rest are marked unresolved. Returns are always computed, so the address
+// after a call is found from the return address the call pushes.
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub blocks: BTreeMap<i64, BasicBlock>,
+    // Instructions that write into decoded code, as (pc, address written).
+    pub code_writes: Vec<(i64, i64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub start: i64,
+    pub instructions: Vec<(i64, Instruction, Vec<i64>)>,
+    pub successors: Vec<Edge>,
+    // Ends in a jump whose target is computed or relative.
+    pub unresolved: bool,
+    // Runs into a word that doesn't decode.
+    pub invalid: bool,
+    // Some instruction writes into this block.
+    pub self_modifying: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub target: i64,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    Taken,
+    FallThrough,
+    // From a call to the address it pushed as its return address.
+    Return,
+}
+
+impl BasicBlock {
+    // The address just past the block's last instruction.
+    pub fn end(&self) -> i64 {
+        match self.instructions.last() {
+            Some((address, instruction, _)) => address + instruction.length(),
+            None => self.start,
+        }
+    }
+}
+
+// Where control can go after a jump, as (taken target, falls through). A
+// target of `None` means the jump can be taken but its target is unknown.
+fn jump_targets(
+    instruction: &Instruction,
+    params: &[i64],
+    len: i64,
+) -> (Option<Option<i64>>, bool) {
+    let (always, never) = match instruction.modes[0] {
+        Mode::Immediate => {
+            let taken = match instruction.opcode {
+                Opcode::JumpIfTrue => params[0] != 0,
+                _ => params[0] == 0,
+            };
+            (taken, !taken)
+        }
+        _ => (false, false),
+    };
+    let target = if never {
+        None
+    } else if instruction.modes[1] == Mode::Immediate && params[1] >= 0 && params[1] < len {
+        Some(Some(params[1]))
+    } else {
+        Some(None)
+    };
+    (target, !always)
+}
+
+// Compiled Intcode calls a routine by storing the return address on the
+// stack and then jumping unconditionally, e.g.
+//
+//     mul #942, #1, rb+0
+//     jz #0, #922
+//
+// Returns the address the call returns to if `store` and `jump` look like
+// that, with `next` the address after the jump.
+fn return_address(
+    store: &Instruction,
+    store_params: &[i64],
+    jump: &Instruction,
+    jump_params: &[i64],
+    next: i64,
+) -> Option<i64> {
+    let value = match store.opcode {
+        Opcode::Add => store_params[0].checked_add(store_params[1])?,
+        Opcode::Multiply => store_params[0].checked_mul(store_params[1])?,
+        _ => return None,
+    };
+    let (_, falls_through) = jump_targets(jump, jump_params, i64::MAX);
+    let call = store.modes == [Mode::Immediate, Mode::Immediate, Mode::Relative]
+        && is_jump(jump.opcode)
+        && !falls_through
+        && value == next;
+    if call {
+        Some(next)
+    } else {
+        None
+    }
+}
+
+fn is_jump(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse)
+}
+
+pub fn recover_cfg(words: &[i64]) -> Cfg {
+    let len = words.len() as i64;
+    let mut decoded: BTreeMap<i64, (Instruction, Vec<i64>)> = BTreeMap::new();
+    let mut leaders: BTreeSet<i64> = BTreeSet::new();
+    let mut worklist = vec![0];
+    leaders.insert(0);
+
+    while let Some(mut address) = worklist.pop() {
+        let mut previous = None;
+        while !decoded.contains_key(&address) {
+            let instruction = match words.get(address as usize) {
+                Some(word) if address >= 0 => Instruction::decode(address, *word).ok(),
+                _ => None,
+            };
+            let instruction = match instruction {
+                Some(instruction) if address + instruction.length() <= len => instruction,
+                _ => break,
+            };
+            let next = address + instruction.length();
+            let params = words[address as usize + 1..next as usize].to_vec();
+            let opcode = instruction.opcode;
+            if is_jump(opcode) {
+                let (target, falls_through) = jump_targets(&instruction, &params, len);
+                if let Some(Some(target)) = target {
+                    leaders.insert(target);
+                    worklist.push(target);
+                }
+                let call = previous.and_then(|store| {
+                    let (store, store_params) = decoded.get(&store)?;
+                    return_address(store, store_params, &instruction, &params, next)
+                });
+                if falls_through || call.is_some() {
+                    leaders.insert(next);
+                    worklist.push(next);
+                }
+            }
+            decoded.insert(address, (instruction, params));
+            if is_jump(opcode) || opcode == Opcode::Halt {
+                break;
+            }
+            previous = Some(address);
+            address = next;
+        }
+    }
+
+    let mut blocks = BTreeMap::new();
+    for &start in leaders.iter() {
+        let mut block = BasicBlock {
+            start,
+            instructions: Vec::new(),
+            successors: Vec::new(),
+            unresolved: false,
+            invalid: false,
+            self_modifying: false,
+        };
+        let mut address = start;
+        loop {
+            let (instruction, params) = match decoded.get(&address) {
+                Some(entry) => entry.clone(),
+                None => {
+                    block.invalid = true;
+                    break;
+                }
+            };
+            block
+                .instructions
+                .push((address, instruction, params.clone()));
+            let next = address + instruction.length();
+            if is_jump(instruction.opcode) {
+                let (target, falls_through) = jump_targets(&instruction, &params, len);
+                match target {
+                    Some(Some(target)) => block.successors.push(Edge {
+                        target,
+                        kind: EdgeKind::Taken,
+                    }),
+                    Some(None) => block.unresolved = true,
+                    None => {}
+                }
+                if falls_through {
+                    block.successors.push(Edge {
+                        target: next,
+                        kind: EdgeKind::FallThrough,
+                    });
+                }
+                let store = block.instructions.iter().rev().nth(1);
+                let call = store.and_then(|(_, store, store_params)| {
+                    return_address(store, store_params, &instruction, &params, next)
+                });
+                if let Some(target) = call {
+                    block.successors.push(Edge {
+                        target,
+                        kind: EdgeKind::Return,
+                    });
+                }
+                break;
+            }
+            if instruction.opcode == Opcode::Halt {
+                break;
+            }
+            if leaders.contains(&next) {
+                block.successors.push(Edge {
+                    target: next,
+                    kind: EdgeKind::FallThrough,
+                });
+                break;
+            }
+            address = next;
+        }
+        blocks.insert(start, block);
+    }
+
+    // Only writes to a fixed address can be checked statically.
+    let mut code_writes = Vec::new();
+    for (&address, (instruction, params)) in decoded.iter() {
+        let last = instruction.opcode.num_params().saturating_sub(1);
+        if !instruction.opcode.writes() || instruction.modes[last] != Mode::Position {
+            continue;
+        }
+        let target = params[last];
+        let written = decoded
+            .range(..=target)
+            .next_back()
+            .filter(|(start, (instruction, _))| target < *start + instruction.length());
+        if written.is_some() {
+            code_writes.push((address, target));
+        }
+    }
+    for block in blocks.values_mut() {
+        block.self_modifying = code_writes
+            .iter()
+            .any(|&(_, target)| target >= block.start && target < block.end());
+    }
+
+    Cfg {
+        blocks,
+        code_writes,
+    }
+}
+
+impl Cfg {
+    // Renders the graph in Graphviz DOT. Self-modifying blocks are red,
+    // invalid ones grey, and unresolved jumps point at a `?` node.
+    pub fn to_dot(&self) -> String {
+        let writers: BTreeSet<i64> = self.code_writes.iter().map(|&(pc, _)| pc).collect();
+        let mut lines = vec![
+            "digraph cfg {".to_string(),
+            "    node [shape=box, fontname=\"monospace\"];".to_string(),
+        ];
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            for (address, instruction, params) in block.instructions.iter() {
+                label.push_str(&format!(
+                    "{:>6}  {}",
+                    address,
+                    format_instruction(instruction, params)
+                ));
+                if writers.contains(address) {
+                    label.push_str("  ; writes code");
+                }
+                label.push_str("\\l");
+            }
+            if block.invalid {
+                label.push_str(&format!("{:>6}  <invalid>\\l", block.end()));
+            }
+            let mut attributes = format!("label=\"{}\"", label);
+            if block.self_modifying {
+                attributes.push_str(", color=red");
+            } else if block.invalid {
+                attributes.push_str(", color=grey");
+            }
+            lines.push(format!("    b{} [{}];", block.start, attributes));
+            for edge in block.successors.iter() {
+                let style = match edge.kind {
+                    EdgeKind::Taken => "",
+                    EdgeKind::FallThrough => " [style=dashed]",
+                    EdgeKind::Return => " [style=dashed, label=\"return\"]",
+                };
+                lines.push(format!(
+                    "    b{} -> b{}{};",
+                    block.start, edge.target, style
+                ));
+            }
+            if block.unresolved {
+                lines.push(format!(
+                    "    u{} [label=\"?\", shape=diamond];",
+                    block.start
+                ));
+                lines.push(format!(
+                    "    b{} -> u{} [style=dotted];",
+                    block.start, block.start
+                ));
+            }
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble_words;
+
+    #[test]
+    fn test() {
+        let source = "
+                    in @x
+            loop:   jz @x, #done
+                    add @x, #-1, @x
+                    add #5, #0, @patch+1
+            patch:  out #0
+                    jnz #1, #loop
+            done:   jnz @x, rb+0
+                    hlt
+            x:      data 0
+        ";
+        let words = assemble_words(source).unwrap();
+        let cfg = recover_cfg(&words);
+
+        let starts: Vec<i64> = cfg.blocks.keys().copied().collect();
+        assert!(starts == vec![0, 2, 5, 18, 21]);
+        assert!(
+            cfg.blocks[&2].successors
+                == vec![
+                    Edge {
+                        target: 18,
+                        kind: EdgeKind::Taken
+                    },
+                    Edge {
+                        target: 5,
+                        kind: EdgeKind::FallThrough
+                    }
+                ]
+        );
+        // `jnz #1` always jumps.
+        assert!(
+            cfg.blocks[&5].successors
+                == vec![Edge {
+                    target: 2,
+                    kind: EdgeKind::Taken
+                }]
+        );
+        assert!(cfg.blocks[&18].unresolved && !cfg.blocks[&2].unresolved);
+        assert!(cfg.blocks[&21].successors.is_empty());
+        assert!(cfg.code_writes == vec![(9, 14)]);
+        assert!(cfg.blocks[&5].self_modifying && !cfg.blocks[&2].self_modifying);
+
+        let dot = cfg.to_dot();
+        assert!(dot.contains("    b2 -> b18;\n    b2 -> b5 [style=dashed];\n"));
+        assert!(dot.contains("     9  add #5, #0, @14  ; writes code\\l"));
+        assert!(dot.contains("    b18 -> u18 [style=dotted];"));
+
+        let source = "
+                    arb #100
+                    add #ret, #0, rb+0
+                    jz #0, #f
+            ret:    hlt
+            f:      jz #0, rb+0
+        ";
+        let cfg = recover_cfg(&assemble_words(source).unwrap());
+        assert!(cfg.blocks.keys().copied().collect::<Vec<i64>>() == vec![0, 9, 10]);
+        assert!(
+            cfg.blocks[&0].successors
+                == vec![
+                    Edge {
+                        target: 10,
+                        kind: EdgeKind::Taken
+                    },
+                    Edge {
+                        target: 9,
+                        kind: EdgeKind::Return
+                    }
+                ]
+        );
+
+        // Falling off the end into a word that doesn't decode.
+        let cfg = recover_cfg(&[1101, 1, 2, 5, 77]);
+        assert!(cfg.blocks[&0].invalid);
+    }
+}
@@ -0,0 +1,1683 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use crate::arith::{Arithmetic, Wide};
+use crate::custom::{CustomOpcode, CustomOpcodes, OpcodeContext};
+use crate::disasm::format_instruction;
+use crate::error::{IntcodeError, Result};
+use crate::fuse::Fusion;
+use crate::history::{Checkpoint, History, Undo};
+use crate::instruction::{decode_modes, Instruction, Mode, Opcode};
+use crate::io::{IntcodeInput, IntcodeOutput};
+#[cfg(feature = "jit")]
+use crate::jit::{JitContext, JitState};
+use crate::memory::Memory;
+use crate::profile::Profile;
+use crate::program::{ParseError, Program};
+use crate::trace::{MemoryWrite, TraceRecord, Tracer};
+
+// Addresses below this have their decoded instruction and whether they've
+// been executed kept in Vecs, which is room for any real program. Code past
+// it is decoded afresh every time, and a set tracks what's been executed.
+const DENSE_ADDRESSES: usize = 1 << 16;
+
+// Why `run` returned. `run_wide` returns a `RunState<Wide>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState<T = i64> {
+    Halted,
+    NeedsInput,
+    Output(T),
+}
+
+// Why one of the bounded runs, like `step` or `run_for`, returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Halted,
+    NeedsInput,
+    // The run reached its limit and the program can carry on.
+    Paused,
+}
+
+// How a bounded run ended, and everything the program printed on the way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunStatus {
+    pub stop: Stop,
+    pub output: Vec<i64>,
+}
+
+// What to do when a program writes to an address it has already executed
+// as part of an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelfModifying {
+    // Allow the write and count it in `Stats::code_writes`.
+    Permissive,
+    // Fail with `IntcodeError::SelfModifyingWrite`.
+    Strict,
+}
+
+// Bounds on what a run may use. Tripping one fails the run with
+// `IntcodeError::InstructionLimit`, `MemoryLimit` or `OutputLimit`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    // The most instructions executed over the computer's life.
+    pub instructions: Option<u64>,
+    // The highest address the program may write to or execute. Memory is
+    // allocated a page at a time for the addresses written, so this also
+    // caps its footprint.
+    pub max_address: Option<i64>,
+    // The most values the program may print.
+    pub outputs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub instructions: u64,
+    pub code_writes: u64,
+}
+
+// An iterator over the values a computer prints, running it only as far
+// as the next one. It ends when the program halts, needs input or fails,
+// and `stop` says which.
+#[derive(Debug)]
+pub struct Outputs<'a> {
+    computer: &'a mut IntCodeComputer,
+    stop: Option<Result<RunState>>,
+}
+
+impl Iterator for Outputs<'_> {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        if self.stop.is_some() {
+            return None;
+        }
+        match self.computer.run() {
+            Ok(RunState::Output(value)) => Some(value),
+            stop => {
+                self.stop = Some(stop);
+                None
+            }
+        }
+    }
+}
+
+impl Outputs<'_> {
+    // The next `N` values as one frame, or `None` if the iterator ends
+    // first. The values of a partial frame are lost.
+    pub fn next_n<const N: usize>(&mut self) -> Option<[i64; N]> {
+        let mut frame = [0; N];
+        for value in frame.iter_mut() {
+            *value = self.next()?;
+        }
+        Some(frame)
+    }
+
+    // Why the iterator ended: `RunState::Halted`, `RunState::NeedsInput`
+    // or the error the program failed with. `None` until it has.
+    pub fn stop(&self) -> Option<Result<RunState>> {
+        self.stop
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IntCodeComputer {
+    memory: Memory,
+    counter: i64,
+    relative_base: i64,
+    input: VecDeque<i64>,
+    halted: bool,
+    // Decoded instructions indexed by address, cleared whenever the
+    // address is written to.
+    cache: Vec<Option<Instruction>>,
+    // Addresses that have been decoded as part of an instruction, below
+    // `DENSE_ADDRESSES` and past it.
+    executed: Vec<bool>,
+    far_executed: HashSet<usize>,
+    self_modifying: SelfModifying,
+    limits: Limits,
+    outputs: u64,
+    stats: Stats,
+    arithmetic: Arithmetic,
+    // Values too wide for an i64, by address. Memory holds them saturated.
+    wide: HashMap<i64, Wide>,
+    // Whether a value too wide for an i64 can be printed, which only
+    // `run_wide` allows, and the last one that was.
+    print_wide: bool,
+    wide_output: Option<Wide>,
+    custom: CustomOpcodes,
+    trace: Option<Arc<Mutex<Tracer>>>,
+    profile: Option<Box<Profile>>,
+    history: Option<Box<History>>,
+    // Superinstructions for the program, shared until one is removed.
+    fusion: Arc<Fusion>,
+    fuse: bool,
+    #[cfg(feature = "jit")]
+    jit: JitState,
+}
+
+// Parses a program as `Program` does and starts a computer on it.
+impl FromStr for IntCodeComputer {
+    type Err = ParseError;
+
+    fn from_str(program: &str) -> std::result::Result<IntCodeComputer, ParseError> {
+        Ok(IntCodeComputer::new(&program.parse()?))
+    }
+}
+
+impl IntCodeComputer {
+    pub fn new(program: &Program) -> IntCodeComputer {
+        IntCodeComputer {
+            memory: program.memory(),
+            counter: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            halted: false,
+            cache: Vec::new(),
+            executed: Vec::new(),
+            far_executed: HashSet::new(),
+            self_modifying: SelfModifying::Permissive,
+            limits: Limits::default(),
+            outputs: 0,
+            stats: Stats::default(),
+            arithmetic: Arithmetic::default(),
+            wide: HashMap::new(),
+            print_wide: false,
+            wide_output: None,
+            custom: CustomOpcodes::default(),
+            trace: None,
+            profile: None,
+            history: None,
+            fusion: program.fusion(),
+            fuse: true,
+            #[cfg(feature = "jit")]
+            jit: JitState::new(program),
+        }
+    }
+
+    // Starts a computer on `program`, panicking if it isn't valid. Parse it
+    // with `str::parse` instead to handle the error.
+    pub fn from_program(program: &str) -> IntCodeComputer {
+        IntCodeComputer::new(&Program::parse(program))
+    }
+
+    // Returns an independent copy of the machine, including pc, relative
+    // base and any queued input. Memory pages stay shared until either copy
+    // writes to them, so forking is cheap enough to do at every step of a
+    // search.
+    pub fn fork(&self) -> IntCodeComputer {
+        self.clone()
+    }
+
+    pub fn pc(&self) -> i64 {
+        self.counter
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    // Negative addresses read as 0, as nothing can be written there.
+    pub fn read(&self, address: i64) -> i64 {
+        if address < 0 {
+            return 0;
+        }
+        self.memory.read(address as usize)
+    }
+
+    // Like `read`, but with the exact value of a word too wide for an i64.
+    pub fn read_wide(&self, address: i64) -> Wide {
+        match self.wide.get(&address) {
+            Some(value) => value.clone(),
+            None => Wide::from(self.read(address)),
+        }
+    }
+
+    // Sets a word of memory from outside the program. Panics if `address`
+    // is negative; a program doing the same fails with
+    // `IntcodeError::NegativeAddress` instead.
+    pub fn write(&mut self, address: i64, value: i64) {
+        assert!(address >= 0, "write to negative address {}", address);
+        if let Some(cached) = self.cache.get_mut(address as usize) {
+            *cached = None;
+        }
+        if self.fusion.covers(address) {
+            Arc::make_mut(&mut self.fusion).invalidate(address);
+        }
+        #[cfg(feature = "jit")]
+        self.jit.invalidate(address);
+        if !self.wide.is_empty() {
+            self.wide.remove(&address);
+        }
+        self.memory.write(address as usize, value);
+    }
+
+    pub fn set_self_modifying(&mut self, policy: SelfModifying) {
+        self.self_modifying = policy;
+    }
+
+    // Compiled code isn't used while any limit is set.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    // Checked arithmetic is the default. Values too wide for an i64 can
+    // only be added, multiplied, compared and printed by `run_wide`; using
+    // one as an address, jump target or relative base adjustment, or
+    // printing one from `run`, is an overflow.
+    // Switching to `Checked` or `Wrapping` leaves any already in memory
+    // saturated.
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.arithmetic = arithmetic;
+        if matches!(arithmetic, Arithmetic::Checked | Arithmetic::Wrapping) {
+            self.wide.clear();
+        }
+    }
+
+    // Adds an instruction for words whose opcode isn't a standard one,
+    // replacing any registered before with the same code. Custom
+    // instructions are interpreted one at a time. Traces and profiles only
+    // cover standard opcodes, so they leave custom instructions out, though
+    // `stats` counts them.
+    pub fn register_opcode<C: CustomOpcode + 'static>(&mut self, opcode: C) {
+        let code = opcode.code();
+        assert!(
+            (0..100).contains(&code) && Opcode::from_code(code).is_none(),
+            "opcode {} isn't free for a custom instruction",
+            code
+        );
+        assert!(
+            opcode.num_params() <= 3,
+            "opcode {} has more than three parameters",
+            code
+        );
+        self.custom.insert(Arc::new(opcode));
+    }
+
+    // Whether `run` executes common instruction sequences as single fused
+    // steps, which it does by default. Tracing, profiling and recording
+    // history never do.
+    pub fn set_fusion(&mut self, enabled: bool) {
+        self.fuse = enabled;
+    }
+
+    // Computers use compiled code when built with the `jit` feature unless
+    // it's turned off here. Tracing, profiling and recording history always
+    // interpret.
+    #[cfg(feature = "jit")]
+    pub fn set_jit(&mut self, enabled: bool) {
+        self.jit.enabled = enabled;
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    pub fn push_input(&mut self, value: i64) {
+        self.input.push_back(value);
+    }
+
+    // The number of input values queued but not yet read.
+    pub fn input_len(&self) -> usize {
+        self.input.len()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    // Writes a JSON-lines record of every instruction executed from now on
+    // to `writer`, except custom ones. Forks share the trace with the
+    // computer they came from.
+    pub fn trace_to<W: Write + Send + 'static>(&mut self, writer: W) {
+        self.trace = Some(Arc::new(Mutex::new(Tracer::new(Box::new(writer)))));
+    }
+
+    // Stops tracing, flushing the trace and reporting the first error hit
+    // while writing it.
+    pub fn finish_trace(&mut self) -> io::Result<()> {
+        match self.trace.take() {
+            Some(tracer) => tracer.lock().unwrap().finish(),
+            None => Ok(()),
+        }
+    }
+
+    // Counts executed instructions from now on, except custom ones; see
+    // `Profile`.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Box::new(Profile::new()));
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    // Records every instruction executed from now on, so that at least the
+    // last `limit` can be undone with `step_back`. Recent instructions are
+    // undone from a log of what they changed, and older ones by replaying
+    // from a checkpoint of the whole machine taken every few thousand
+    // instructions, so the history takes the same memory however long the
+    // program runs. Replaying runs custom instructions again. Values
+    // written with `write` aren't part of the history and may be lost by
+    // stepping back past them. A limit of 0 is taken as 1.
+    pub fn enable_history(&mut self, limit: u64) {
+        let limit = limit.max(1);
+        self.history = Some(Box::new(History::new(limit, self.checkpoint())));
+    }
+
+    // Undoes the last `n` instructions, or as many as the history reaches
+    // back, returning how many were undone. Output stays printed, but
+    // input that was read is queued again.
+    pub fn step_back(&mut self, n: u64) -> Result<u64> {
+        let now = self.stats.instructions;
+        let start = match &self.history {
+            Some(history) => history.start(now),
+            None => return Ok(0),
+        };
+        let target = now - n.min(now - start);
+        self.rewind_to(target)?;
+        Ok(now - target)
+    }
+
+    // Steps back to the last instruction that wrote to `address`, so it's
+    // the next to execute. Returns false, having stepped back as far as
+    // the history reaches, if none did.
+    pub fn step_back_to_write(&mut self, address: i64) -> Result<bool> {
+        if self.history.is_none() {
+            return Ok(false);
+        }
+        loop {
+            while let Some(undo) = self.undo() {
+                if undo.writes.iter().any(|write| write.0 == address) {
+                    return Ok(true);
+                }
+            }
+            // The undo log ran out, so rebuild it from the checkpoint before,
+            // all the way back to that checkpoint.
+            let now = self.stats.instructions;
+            let history = self.history.as_ref().unwrap();
+            let index = match now.checked_sub(1) {
+                Some(before) => history.checkpoint_before(before),
+                None => None,
+            };
+            match index {
+                Some(index) => self.restore(index),
+                None => return Ok(false),
+            }
+            self.history.as_mut().unwrap().rebuilding = true;
+            let replayed = self.replay_to(now);
+            self.history.as_mut().unwrap().rebuilding = false;
+            replayed?;
+        }
+    }
+
+    // Runs until the program halts, needs input that hasn't been pushed
+    // yet, or prints a value. Calling `run` again resumes where it left off.
+    pub fn run(&mut self) -> Result<RunState> {
+        loop {
+            #[cfg(feature = "jit")]
+            {
+                if self.run_compiled() {
+                    continue;
+                }
+            }
+            if self.run_fused()? {
+                continue;
+            }
+            if let Some(state) = self.single_step()? {
+                return Ok(state);
+            }
+        }
+    }
+
+    // Runs as `run` does, but prints values too wide for an i64 instead of
+    // failing on them.
+    pub fn run_wide(&mut self) -> Result<RunState<Wide>> {
+        self.print_wide = true;
+        self.wide_output = None;
+        let state = self.run();
+        self.print_wide = false;
+        Ok(match state? {
+            RunState::Halted => RunState::Halted,
+            RunState::NeedsInput => RunState::NeedsInput,
+            RunState::Output(value) => match self.wide_output.take() {
+                Some(wide) => RunState::Output(wide),
+                None => RunState::Output(Wide::from(value)),
+            },
+        })
+    }
+
+    // Executes exactly one instruction, unless the program has halted or
+    // is waiting for input.
+    pub fn step(&mut self) -> Result<RunStatus> {
+        self.run_for(1)
+    }
+
+    // Executes up to `steps` instructions.
+    pub fn run_for(&mut self, steps: u64) -> Result<RunStatus> {
+        self.run_bounded(|_, executed, _| executed == steps)
+    }
+
+    // Runs until the program has printed `n` values.
+    pub fn run_until_outputs(&mut self, n: usize) -> Result<RunStatus> {
+        self.run_bounded(|_, _, output| output.len() == n)
+    }
+
+    // Runs until the pc reaches `address`. At least one instruction is
+    // executed, so this can be called again to get there the next time.
+    pub fn run_until_pc(&mut self, address: i64) -> Result<RunStatus> {
+        self.run_bounded(|computer, executed, _| executed > 0 && computer.counter == address)
+    }
+
+    // Executes instructions one at a time until `pause` returns true. It's
+    // called before each instruction with the number executed so far and
+    // the output printed. Fused handlers and compiled code are never used,
+    // so `pause` sees every instruction boundary.
+    fn run_bounded<F>(&mut self, mut pause: F) -> Result<RunStatus>
+    where
+        F: FnMut(&IntCodeComputer, u64, &[i64]) -> bool,
+    {
+        let mut output = Vec::new();
+        let mut executed = 0;
+        loop {
+            if pause(self, executed, &output) {
+                return Ok(RunStatus {
+                    stop: Stop::Paused,
+                    output,
+                });
+            }
+            let stop = match self.single_step()? {
+                Some(RunState::Halted) => Stop::Halted,
+                Some(RunState::NeedsInput) => Stop::NeedsInput,
+                Some(RunState::Output(value)) => {
+                    output.push(value);
+                    Stop::Paused
+                }
+                None => Stop::Paused,
+            };
+            if stop != Stop::Paused {
+                return Ok(RunStatus { stop, output });
+            }
+            executed += 1;
+        }
+    }
+
+    // Executes a single instruction. Returns the state the machine stopped
+    // in if that instruction halted, blocked on input or printed a value,
+    // and `None` otherwise.
+    fn single_step(&mut self) -> Result<Option<RunState>> {
+        if self.halted {
+            return Ok(Some(RunState::Halted));
+        }
+        if self.history.is_some() {
+            return self.recorded_step();
+        }
+        self.execute_next()
+    }
+
+    // Executes the instruction at the pc, as `single_step` does once it
+    // knows the program hasn't halted.
+    fn execute_next(&mut self) -> Result<Option<RunState>> {
+        let instruction = match self.decode() {
+            Ok(instruction) => instruction,
+            Err(IntcodeError::InvalidOpcode { .. }) if !self.custom.is_empty() => {
+                return self.execute_custom();
+            }
+            Err(error) => return Err(error),
+        };
+        let (args, addresses) =
+            self.get_args(&instruction.modes[..instruction.opcode.num_params()])?;
+        if self.trace.is_none() && self.profile.is_none() {
+            return self.execute(&instruction, args, addresses);
+        }
+
+        let pc = self.counter;
+        let relative_base = self.relative_base;
+        let state = if self.trace.is_some() {
+            self.traced_execute(&instruction, args, addresses)?
+        } else {
+            self.execute(&instruction, args, addresses)?
+        };
+        if state != Some(RunState::NeedsInput) {
+            if let Some(mut profile) = self.profile.take() {
+                let text = || self.format_at(pc, &instruction);
+                profile.record(
+                    pc,
+                    &instruction,
+                    args[0],
+                    relative_base,
+                    self.relative_base,
+                    text,
+                );
+                self.profile = Some(profile);
+            }
+        }
+        Ok(state)
+    }
+
+    // `single_step` for a computer recording history. An instruction that
+    // fails is undone, leaving the computer just before it.
+    fn recorded_step(&mut self) -> Result<Option<RunState>> {
+        let input = self.input.front().copied();
+        let queued = self.input.len();
+        if let Some(history) = &mut self.history {
+            history.pending = Some(Undo {
+                pc: self.counter,
+                relative_base: self.relative_base,
+                stats: self.stats,
+                outputs: self.outputs,
+                writes: Vec::new(),
+                input: None,
+            });
+        }
+        let state = self.execute_next();
+        let mut history = self.history.take().unwrap();
+        let mut undo = history.pending.take().unwrap();
+        if self.input.len() < queued {
+            undo.input = input;
+        }
+        match state {
+            Err(_) => self.apply_undo(&undo),
+            Ok(Some(RunState::NeedsInput)) => {}
+            Ok(_) => {
+                history.commit(undo);
+                if history.checkpoint_due(self.stats.instructions) {
+                    history.push_checkpoint(self.checkpoint());
+                }
+            }
+        }
+        self.history = Some(history);
+        state
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            memory: self.memory.clone(),
+            counter: self.counter,
+            relative_base: self.relative_base,
+            halted: self.halted,
+            wide: self.wide.clone(),
+            outputs: self.outputs,
+            stats: self.stats,
+        }
+    }
+
+    // Undoes the last instruction in the undo log, returning what it did.
+    fn undo(&mut self) -> Option<Undo> {
+        let undo = self.history.as_mut()?.pop()?;
+        self.apply_undo(&undo);
+        Some(undo)
+    }
+
+    fn apply_undo(&mut self, undo: &Undo) {
+        for (address, old, wide) in undo.writes.iter().rev() {
+            self.write(*address, *old);
+            if let Some(wide) = wide {
+                self.wide.insert(*address, wide.clone());
+            }
+        }
+        if let Some(value) = undo.input {
+            self.input.push_front(value);
+        }
+        self.counter = undo.pc;
+        self.relative_base = undo.relative_base;
+        self.stats = undo.stats;
+        self.outputs = undo.outputs;
+        self.halted = false;
+    }
+
+    // Goes back or forward to the point where `target` instructions had
+    // been executed, which the history must reach.
+    fn rewind_to(&mut self, target: u64) -> Result<()> {
+        let history = self.history.as_ref().unwrap();
+        if !history.logged(self.stats.instructions, target) {
+            let index = history.checkpoint_before(target).unwrap();
+            self.restore(index);
+        }
+        while self.stats.instructions > target {
+            self.undo();
+        }
+        self.replay_to(target)
+    }
+
+    // Puts the machine back the way it was at checkpoint `index`, with the
+    // input read since queued again.
+    fn restore(&mut self, index: usize) {
+        let (checkpoint, mut input) = self.history.as_mut().unwrap().rewind(index);
+        input.extend(self.input.drain(..));
+        self.input = input;
+        self.memory = checkpoint.memory;
+        self.counter = checkpoint.counter;
+        self.relative_base = checkpoint.relative_base;
+        self.halted = checkpoint.halted;
+        self.wide = checkpoint.wide;
+        self.outputs = checkpoint.outputs;
+        self.stats = checkpoint.stats;
+        // Memory was swapped out without going through `write`. Fused
+        // handlers and compiled blocks are never added while recording, so
+        // any covering words written since the checkpoint are gone already.
+        self.cache.clear();
+    }
+
+    // Executes instructions until `target` have been, without tracing or
+    // profiling them again.
+    fn replay_to(&mut self, target: u64) -> Result<()> {
+        let trace = self.trace.take();
+        let profile = self.profile.take();
+        // Wide values among those printed must have been printed by
+        // `run_wide` the first time.
+        let print_wide = self.print_wide;
+        self.print_wide = true;
+        let mut result = Ok(());
+        while result.is_ok() && self.stats.instructions < target {
+            result = match self.single_step() {
+                Ok(Some(RunState::NeedsInput)) => Err(IntcodeError::InputExhausted {
+                    pc: self.counter,
+                    instruction: self.read(self.counter),
+                }),
+                Ok(_) => Ok(()),
+                Err(error) => Err(error),
+            };
+        }
+        self.trace = trace;
+        self.profile = profile;
+        self.print_wide = print_wide;
+        self.wide_output = None;
+        result
+    }
+
+    // Inlined so the fused handler loop stays as fast as single steps.
+    #[inline(always)]
+    fn execute(
+        &mut self,
+        instruction: &Instruction,
+        args: [i64; 3],
+        addresses: [i64; 3],
+    ) -> Result<Option<RunState>> {
+        if instruction.opcode == Opcode::Input && self.input.is_empty() {
+            return Ok(Some(RunState::NeedsInput));
+        }
+        self.count_instruction()?;
+        match instruction.opcode {
+            Opcode::Add => {
+                let (value, overflowed) = args[0].overflowing_add(args[1]);
+                if overflowed || !self.wide.is_empty() {
+                    self.wide_arithmetic(instruction, args, addresses)?;
+                } else {
+                    self.store(addresses[2], value)?;
+                }
+            }
+            Opcode::Multiply => {
+                let (value, overflowed) = args[0].overflowing_mul(args[1]);
+                if overflowed || !self.wide.is_empty() {
+                    self.wide_arithmetic(instruction, args, addresses)?;
+                } else {
+                    self.store(addresses[2], value)?;
+                }
+            }
+            Opcode::Input => {
+                let value = self.input.pop_front().unwrap();
+                self.store(addresses[0], value)?;
+            }
+            Opcode::Output => {
+                if !self.wide.is_empty() {
+                    self.print_wide_operand(instruction, addresses)?;
+                }
+                self.count_output()?;
+                self.counter += instruction.length();
+                return Ok(Some(RunState::Output(args[0])));
+            }
+            Opcode::JumpIfTrue => {
+                if args[0] != 0 {
+                    self.check_narrow(instruction, 1, addresses)?;
+                    self.counter = args[1];
+                    return Ok(None);
+                }
+            }
+            Opcode::JumpIfFalse => {
+                if args[0] == 0 {
+                    self.check_narrow(instruction, 1, addresses)?;
+                    self.counter = args[1];
+                    return Ok(None);
+                }
+            }
+            Opcode::LessThan | Opcode::Equals if !self.wide.is_empty() => {
+                self.wide_arithmetic(instruction, args, addresses)?
+            }
+            Opcode::LessThan => {
+                let res = if args[0] < args[1] { 1 } else { 0 };
+                self.store(addresses[2], res)?;
+            }
+            Opcode::Equals => {
+                let res = if args[0] == args[1] { 1 } else { 0 };
+                self.store(addresses[2], res)?;
+            }
+            Opcode::AdjustRelativeBase => {
+                self.check_narrow(instruction, 0, addresses)?;
+                self.relative_base = match self.relative_base.checked_add(args[0]) {
+                    Some(relative_base) => relative_base,
+                    None => return Err(self.overflow()),
+                };
+            }
+            Opcode::Halt => {
+                self.halted = true;
+                return Ok(Some(RunState::Halted));
+            }
+        }
+        self.counter += instruction.length();
+        Ok(None)
+    }
+
+    fn count_instruction(&mut self) -> Result<()> {
+        if let Some(limit) = self.limits.instructions {
+            if self.stats.instructions >= limit {
+                return Err(IntcodeError::InstructionLimit {
+                    pc: self.counter,
+                    instruction: self.read(self.counter),
+                    limit,
+                });
+            }
+        }
+        self.stats.instructions += 1;
+        Ok(())
+    }
+
+    pub(crate) fn count_output(&mut self) -> Result<()> {
+        if let Some(limit) = self.limits.outputs {
+            if self.outputs >= limit {
+                return Err(IntcodeError::OutputLimit {
+                    pc: self.counter,
+                    instruction: self.read(self.counter),
+                    limit,
+                });
+            }
+        }
+        self.outputs += 1;
+        Ok(())
+    }
+
+    // Executes the custom instruction at the pc, failing as an invalid
+    // opcode if there isn't one.
+    fn execute_custom(&mut self) -> Result<Option<RunState>> {
+        let pc = self.counter;
+        let word = self.read(pc);
+        let opcode = self.custom.get(pc, word)?;
+        let num_params = opcode.num_params();
+        let modes = decode_modes(pc, word)?;
+        let modes = &modes[..num_params];
+        if (0..num_params).any(|i| opcode.writes(i) && modes[i] == Mode::Immediate) {
+            return Err(IntcodeError::ImmediateWrite {
+                pc,
+                instruction: word,
+            });
+        }
+        let (args, addresses) = self.get_args(modes)?;
+        self.count_instruction()?;
+        let next = pc + num_params as i64 + 1;
+        self.mark_executed(pc as usize, next as usize);
+        let mut context = OpcodeContext {
+            computer: self,
+            opcode: opcode.as_ref(),
+            args,
+            addresses,
+            next,
+            output: None,
+        };
+        opcode.execute(&mut context)?;
+        let (next, output) = (context.next, context.output);
+        self.counter = next;
+        Ok(output.map(RunState::Output))
+    }
+
+    // Executes `instruction` and writes a trace record for it, unless it
+    // blocked on input and so didn't execute.
+    fn traced_execute(
+        &mut self,
+        instruction: &Instruction,
+        args: [i64; 3],
+        addresses: [i64; 3],
+    ) -> Result<Option<RunState>> {
+        let pc = self.counter;
+        let relative_base = self.relative_base;
+        let num_params = instruction.opcode.num_params();
+        let target = if instruction.opcode.writes() {
+            Some(addresses[num_params - 1])
+        } else {
+            None
+        };
+        let old = target.map(|address| self.read(address));
+        let input = match instruction.opcode {
+            Opcode::Input => self.input.front().copied(),
+            _ => None,
+        };
+
+        let state = self.execute(instruction, args, addresses)?;
+        if state == Some(RunState::NeedsInput) {
+            return Ok(state);
+        }
+
+        let mut record_args = args[..num_params].to_vec();
+        let mut writes = Vec::new();
+        if let (Some(address), Some(old)) = (target, old) {
+            record_args[num_params - 1] = address;
+            writes.push(MemoryWrite {
+                address,
+                old,
+                new: self.read(address),
+            });
+        }
+        let output = match state {
+            Some(RunState::Output(value)) => Some(value),
+            _ => None,
+        };
+        let record = TraceRecord {
+            pc,
+            opcode: instruction.opcode,
+            modes: instruction.modes[..num_params].to_vec(),
+            args: record_args,
+            writes,
+            relative_base,
+            input,
+            output,
+        };
+        if let Some(tracer) = &self.trace {
+            tracer.lock().unwrap().record(&record);
+        }
+        Ok(state)
+    }
+
+    // Output produced by resuming the computer as each value is asked for.
+    pub fn outputs(&mut self) -> Outputs<'_> {
+        Outputs {
+            computer: self,
+            stop: None,
+        }
+    }
+
+    // Runs until the program halts, taking input from `input` whenever it
+    // needs some and writing everything it prints to `output`. Returns
+    // `RunState::NeedsInput` if `input` has nothing to give.
+    pub fn run_with<I, O>(&mut self, input: &mut I, output: &mut O) -> Result<RunState>
+    where
+        I: IntcodeInput + ?Sized,
+        O: IntcodeOutput + ?Sized,
+    {
+        loop {
+            match self.run()? {
+                RunState::Output(value) => output.write_output(value),
+                RunState::NeedsInput => match input.read_input() {
+                    Some(value) => self.push_input(value),
+                    None => return Ok(RunState::NeedsInput),
+                },
+                RunState::Halted => return Ok(RunState::Halted),
+            }
+        }
+    }
+
+    // Queues `input` and runs the program to completion, collecting its
+    // output. Blocking on input is an error here.
+    pub fn run_to_halt(&mut self, input: &[i64]) -> Result<Vec<i64>> {
+        self.collect_output(input, IntCodeComputer::run)
+    }
+
+    // As `run_to_halt`, with `run_wide`.
+    pub fn run_to_halt_wide(&mut self, input: &[i64]) -> Result<Vec<Wide>> {
+        self.collect_output(input, IntCodeComputer::run_wide)
+    }
+
+    fn collect_output<T>(
+        &mut self,
+        input: &[i64],
+        run: fn(&mut IntCodeComputer) -> Result<RunState<T>>,
+    ) -> Result<Vec<T>> {
+        self.input.extend(input);
+        let mut output = Vec::new();
+        loop {
+            match run(self)? {
+                RunState::Output(value) => output.push(value),
+                RunState::Halted => return Ok(output),
+                RunState::NeedsInput => {
+                    return Err(IntcodeError::InputExhausted {
+                        pc: self.counter,
+                        instruction: self.read(self.counter),
+                    })
+                }
+            }
+        }
+    }
+
+    fn decode(&mut self) -> Result<Instruction> {
+        if self.counter < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                pc: self.counter,
+                instruction: 0,
+                address: self.counter,
+            });
+        }
+        self.check_pc()?;
+        let index = self.counter as usize;
+        if let Some(Some(instruction)) = self.cache.get(index) {
+            return Ok(*instruction);
+        }
+        let instruction = Instruction::decode(self.counter, self.read(self.counter))?;
+        if index < DENSE_ADDRESSES {
+            if index >= self.cache.len() {
+                self.cache.resize(index + 1, None);
+            }
+            self.cache[index] = Some(instruction);
+        }
+        self.mark_executed(index, index + instruction.length() as usize);
+        Ok(instruction)
+    }
+
+    // Fails if the pc has run past the memory limit.
+    fn check_pc(&self) -> Result<()> {
+        if let Some(max_address) = self.limits.max_address {
+            if self.counter > max_address {
+                return Err(IntcodeError::MemoryLimit {
+                    pc: self.counter,
+                    instruction: self.read(self.counter),
+                    address: self.counter,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn mark_executed(&mut self, start: usize, end: usize) {
+        let dense_end = end.min(DENSE_ADDRESSES);
+        if dense_end > self.executed.len() {
+            self.executed.resize(dense_end, false);
+        }
+        for executed in &mut self.executed[start.min(dense_end)..dense_end] {
+            *executed = true;
+        }
+        if end > DENSE_ADDRESSES {
+            self.far_executed.extend(start.max(DENSE_ADDRESSES)..end);
+        }
+    }
+
+    fn is_executed(&self, address: usize) -> bool {
+        match self.executed.get(address) {
+            Some(&executed) => executed,
+            None => address >= DENSE_ADDRESSES && self.far_executed.contains(&address),
+        }
+    }
+
+    // Writes on behalf of the running program, checking for writes into
+    // code that has already run.
+    pub(crate) fn store(&mut self, address: i64, value: i64) -> Result<()> {
+        if let Some(max_address) = self.limits.max_address {
+            if address > max_address {
+                return Err(IntcodeError::MemoryLimit {
+                    pc: self.counter,
+                    instruction: self.read(self.counter),
+                    address,
+                });
+            }
+        }
+        if self.history.is_some() {
+            self.record_write(address);
+        }
+        if !self.checked_write(address, value, false) {
+            return Err(IntcodeError::SelfModifyingWrite {
+                pc: self.counter,
+                instruction: self.read(self.counter),
+                address,
+            });
+        }
+        Ok(())
+    }
+
+    // Counts writes to executed code, returning false without writing if
+    // the self-modifying code policy forbids them. `executed` marks the
+    // address as executed even if the computer doesn't know it yet.
+    pub(crate) fn checked_write(&mut self, address: i64, value: i64, executed: bool) -> bool {
+        if executed || self.is_executed(address as usize) {
+            if self.self_modifying == SelfModifying::Strict {
+                return false;
+            }
+            self.stats.code_writes += 1;
+        }
+        self.write(address, value);
+        true
+    }
+
+    #[cold]
+    fn record_write(&mut self, address: i64) {
+        let old = self.read(address);
+        let wide = self.wide.get(&address).cloned();
+        if let Some(undo) = self
+            .history
+            .as_mut()
+            .and_then(|history| history.pending.as_mut())
+        {
+            undo.writes.push((address, old, wide));
+        }
+    }
+
+    #[cold]
+    fn overflow(&self) -> IntcodeError {
+        IntcodeError::Overflow {
+            pc: self.counter,
+            instruction: self.read(self.counter),
+        }
+    }
+
+    // The wide value of parameter `i`, if it has one. Immediates are read
+    // from the instruction itself.
+    fn wide_operand(
+        &self,
+        instruction: &Instruction,
+        i: usize,
+        addresses: [i64; 3],
+    ) -> Option<&Wide> {
+        let address = match instruction.modes[i] {
+            Mode::Immediate => self.counter + i as i64 + 1,
+            _ => addresses[i],
+        };
+        self.wide.get(&address)
+    }
+
+    // Fails if a position or relative mode parameter is wide, as the
+    // address it holds would be read saturated.
+    #[cold]
+    fn check_wide_params(&self, modes: &[Mode]) -> Result<()> {
+        for (i, mode) in modes.iter().enumerate() {
+            let param = self.counter + i as i64 + 1;
+            if *mode != Mode::Immediate && self.wide.contains_key(&param) {
+                return Err(self.overflow());
+            }
+        }
+        Ok(())
+    }
+
+    // Keeps the value an `out` is about to print if it's wide, for
+    // `run_wide` to return, or fails if it's `run` printing it.
+    #[cold]
+    fn print_wide_operand(&mut self, instruction: &Instruction, addresses: [i64; 3]) -> Result<()> {
+        if let Some(value) = self.wide_operand(instruction, 0, addresses) {
+            if !self.print_wide {
+                return Err(self.overflow());
+            }
+            self.wide_output = Some(value.clone());
+        }
+        Ok(())
+    }
+
+    // Fails if operand `i` is wide and about to be used as a jump target
+    // or relative base adjustment, where only an i64 will do.
+    fn check_narrow(&self, instruction: &Instruction, i: usize, addresses: [i64; 3]) -> Result<()> {
+        if !self.wide.is_empty() && self.wide_operand(instruction, i, addresses).is_some() {
+            return Err(self.overflow());
+        }
+        Ok(())
+    }
+
+    // Executes an add, multiply or comparison that overflowed or might
+    // involve wide values, according to the arithmetic policy.
+    #[cold]
+    fn wide_arithmetic(
+        &mut self,
+        instruction: &Instruction,
+        args: [i64; 3],
+        addresses: [i64; 3],
+    ) -> Result<()> {
+        if self.arithmetic == Arithmetic::Wrapping {
+            let value = match instruction.opcode {
+                Opcode::Add => args[0].wrapping_add(args[1]),
+                _ => args[0].wrapping_mul(args[1]),
+            };
+            return self.store(addresses[2], value);
+        }
+        let operand = |i| match self.wide_operand(instruction, i, addresses) {
+            Some(value) => value.clone(),
+            None => Wide::from(args[i]),
+        };
+        let (a, b) = (operand(0), operand(1));
+        let value = match instruction.opcode {
+            Opcode::Add => a.checked_add(&b, self.arithmetic),
+            Opcode::Multiply => a.checked_mul(&b, self.arithmetic),
+            Opcode::LessThan => Some(Wide::from((a < b) as i64)),
+            _ => Some(Wide::from((a == b) as i64)),
+        };
+        let value = value.ok_or_else(|| self.overflow())?;
+        match value.to_i64() {
+            Some(value) => self.store(addresses[2], value),
+            None => {
+                self.store(addresses[2], value.saturate())?;
+                self.wide.insert(addresses[2], value);
+                Ok(())
+            }
+        }
+    }
+
+    // Runs the fused handler at the pc, if there is one and nothing needs
+    // to see individual instructions. Returns false if the interpreter
+    // should execute the next instruction instead.
+    fn run_fused(&mut self) -> Result<bool> {
+        if !self.fuse || self.trace.is_some() || self.profile.is_some() || self.history.is_some() {
+            return Ok(false);
+        }
+        let fused = match self.fusion.get(self.counter) {
+            Some(fused) => *fused,
+            None => return Ok(false),
+        };
+        for part in fused.parts() {
+            self.check_pc()?;
+            let start = part.pc as usize;
+            self.mark_executed(start, start + part.instruction.length() as usize);
+            let modes = &part.instruction.modes[..part.instruction.opcode.num_params()];
+            let (args, addresses) = self.resolve(modes, part.params)?;
+            self.execute(&part.instruction, args, addresses)?;
+            // Writing to the handler removes it. The interpreter carries on
+            // from the next instruction, reading it from memory.
+            if part.instruction.opcode.writes() && self.fusion.get(fused.start()).is_none() {
+                break;
+            }
+        }
+        Ok(true)
+    }
+
+    // Runs compiled code for the block at the pc, if there is any and
+    // nothing needs to see individual instructions. Returns false if the
+    // interpreter should execute the next instruction instead.
+    #[cfg(feature = "jit")]
+    fn run_compiled(&mut self) -> bool {
+        let limited = self.limits != Limits::default();
+        // Compiled code only knows the i64 in memory.
+        if !self.jit.enabled
+            || self.halted
+            || limited
+            || !self.wide.is_empty()
+            || self.trace.is_some()
+            || self.profile.is_some()
+            || self.history.is_some()
+        {
+            return false;
+        }
+        let memory = &self.memory;
+        let read = |address: i64| {
+            if address < 0 {
+                0
+            } else {
+                memory.read(address as usize)
+            }
+        };
+        let block = match self.jit.block(self.counter, read) {
+            Some(block) => block,
+            None => return false,
+        };
+        let mut context = JitContext {
+            computer: self as *mut IntCodeComputer,
+            relative_base: self.relative_base,
+            completed: 0,
+            start: block.start,
+            end: block.end,
+        };
+        let pc = (block.function)(&mut context);
+        let completed = context.completed as usize;
+        if completed == 0 {
+            return false;
+        }
+        if !block.marked {
+            // A block that stops early returns the address just past the
+            // last instruction it completed.
+            let end = if completed == block.instructions {
+                self.jit.set_marked(block.start);
+                block.end as usize
+            } else {
+                pc as usize
+            };
+            self.mark_executed(block.start as usize, end);
+        }
+        self.counter = pc;
+        self.relative_base = context.relative_base;
+        self.stats.instructions += completed as u64;
+        true
+    }
+
+    // Resolves the parameters of the instruction at the pc, which have the
+    // given modes, returning their values and, for position and relative
+    // mode, the addresses they refer to.
+    fn get_args(&self, modes: &[Mode]) -> Result<([i64; 3], [i64; 3])> {
+        let mut params = [0; 3];
+        for (i, param) in params[..modes.len()].iter_mut().enumerate() {
+            *param = self.read(self.counter + i as i64 + 1);
+        }
+        if !self.wide.is_empty() {
+            self.check_wide_params(modes)?;
+        }
+        self.resolve(modes, params)
+    }
+
+    // Like `get_args`, but with the parameter words already read.
+    fn resolve(&self, modes: &[Mode], params: [i64; 3]) -> Result<([i64; 3], [i64; 3])> {
+        let mut args = [0; 3];
+        let mut addresses = [0; 3];
+        for (i, mode) in modes.iter().enumerate() {
+            let mut address = params[i];
+            args[i] = match mode {
+                Mode::Position => self.read_address(address)?,
+                Mode::Immediate => address,
+                Mode::Relative => {
+                    address += self.relative_base;
+                    self.read_address(address)?
+                }
+            };
+            addresses[i] = address;
+        }
+        Ok((args, addresses))
+    }
+
+    fn format_at(&self, pc: i64, instruction: &Instruction) -> String {
+        let params: Vec<i64> = (1..instruction.length())
+            .map(|offset| self.read(pc + offset))
+            .collect();
+        format_instruction(instruction, &params)
+    }
+
+    fn read_address(&self, address: i64) -> Result<i64> {
+        if address < 0 {
+            return Err(IntcodeError::NegativeAddress {
+                pc: self.counter,
+                instruction: self.read(self.counter),
+                address,
+            });
+        }
+        Ok(self.read(address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::testutil::SharedBuffer;
+
+    #[test]
+    fn test() {
+        let mut computer = IntCodeComputer::from_program("1,9,10,3,2,3,11,0,99,30,40,50");
+        computer.run_to_halt(&[]).unwrap();
+        assert!(computer.read(0) == 3500);
+
+        let mut computer = IntCodeComputer::from_program("3,9,8,9,10,9,4,9,99,-1,8");
+        assert!(computer.run_to_halt(&[8]).unwrap() == vec![1]);
+
+        let quine = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+        let mut computer = IntCodeComputer::from_program(quine);
+        let output: Vec<String> = computer
+            .run_to_halt(&[])
+            .unwrap()
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        assert!(output.join(",") == quine);
+
+        let mut computer: IntCodeComputer = "1,0,0,0,99".parse().unwrap();
+        computer.run_to_halt(&[]).unwrap();
+        assert!(computer.read(0) == 2);
+        let error = "1,0,\n0,x".parse::<IntCodeComputer>().unwrap_err();
+        assert!(error.line == 2 && error.column == 3);
+
+        let mut computer = IntCodeComputer::from_program("1101,1,1,1000000000000000,99");
+        computer.run_to_halt(&[]).unwrap();
+        assert!(computer.read(1_000_000_000_000_000) == 2);
+    }
+
+    #[test]
+    fn run_state() {
+        let mut computer = IntCodeComputer::from_program("3,13,3,14,1,13,14,15,4,15,99,0,0,0,0,0");
+        assert!(computer.run().unwrap() == RunState::NeedsInput);
+        computer.push_input(2);
+        assert!(computer.run().unwrap() == RunState::NeedsInput);
+        computer.push_input(3);
+        assert!(computer.run().unwrap() == RunState::Output(5));
+        assert!(!computer.is_halted());
+        assert!(computer.run().unwrap() == RunState::Halted);
+        assert!(computer.is_halted());
+        assert!(computer.run().unwrap() == RunState::Halted);
+    }
+
+    #[test]
+    fn self_modifying() {
+        // Overwrites the `out` at address 0 with position mode after it
+        // has already been executed once.
+        let program = "104,14,3,15,1006,15,14,1101,0,4,0,1105,1,0,99,0";
+        let mut computer = IntCodeComputer::from_program(program);
+        assert!(computer.run().unwrap() == RunState::Output(14));
+        computer.push_input(1);
+        assert!(computer.run().unwrap() == RunState::Output(99));
+        assert!(computer.run().unwrap() == RunState::NeedsInput);
+        computer.push_input(0);
+        assert!(computer.run().unwrap() == RunState::Halted);
+        assert!(
+            computer.stats()
+                == Stats {
+                    instructions: 9,
+                    code_writes: 1
+                }
+        );
+
+        let mut computer = IntCodeComputer::from_program(program);
+        computer.set_self_modifying(SelfModifying::Strict);
+        assert!(computer.run().unwrap() == RunState::Output(14));
+        computer.push_input(1);
+        assert!(
+            computer.run().unwrap_err()
+                == IntcodeError::SelfModifyingWrite {
+                    pc: 7,
+                    instruction: 1101,
+                    address: 0
+                }
+        );
+        assert!(computer.read(0) == 104);
+
+        // Does the same to code it wrote far away, which jumps straight back.
+        let program = "1101,0,1106,1000000000000,1101,0,11,1000000000002,\
+                       1105,1,1000000000000,1101,0,99,1000000000000,99";
+        let mut computer = IntCodeComputer::from_program(program);
+        computer.set_self_modifying(SelfModifying::Strict);
+        assert!(
+            computer.run().unwrap_err()
+                == IntcodeError::SelfModifyingWrite {
+                    pc: 11,
+                    instruction: 1101,
+                    address: 1_000_000_000_000
+                }
+        );
+    }
+
+    #[test]
+    fn fork() {
+        // Adds every input to a running total and prints it.
+        let program = "3,11,1,11,12,12,4,12,1105,1,0,0,0";
+        let mut computer = IntCodeComputer::from_program(program);
+        computer.push_input(5);
+        assert!(computer.run().unwrap() == RunState::Output(5));
+        computer.push_input(1);
+
+        let mut fork = computer.fork();
+        fork.push_input(10);
+        assert!(fork.run().unwrap() == RunState::Output(6));
+        assert!(fork.run().unwrap() == RunState::Output(16));
+
+        assert!(computer.run().unwrap() == RunState::Output(6));
+        assert!(computer.run().unwrap() == RunState::NeedsInput);
+        assert!(computer.read(12) == 6);
+        assert!(fork.read(12) == 16);
+    }
+
+    #[test]
+    fn errors() {
+        let run = |program: &str, input: &[i64]| {
+            IntCodeComputer::from_program(program)
+                .run_to_halt(input)
+                .unwrap_err()
+        };
+        assert!(
+            run("1,0,0,0,42", &[])
+                == IntcodeError::InvalidOpcode {
+                    pc: 4,
+                    instruction: 42
+                }
+        );
+        assert!(
+            run("-1", &[])
+                == IntcodeError::InvalidOpcode {
+                    pc: 0,
+                    instruction: -1
+                }
+        );
+        assert!(
+            run("1,0,0,0", &[])
+                == IntcodeError::InvalidOpcode {
+                    pc: 4,
+                    instruction: 0
+                }
+        );
+        assert!(
+            run("301,0,0,0,99", &[])
+                == IntcodeError::InvalidMode {
+                    pc: 0,
+                    instruction: 301,
+                    mode: 3
+                }
+        );
+        assert!(
+            run("11101,0,0,0,99", &[])
+                == IntcodeError::ImmediateWrite {
+                    pc: 0,
+                    instruction: 11101
+                }
+        );
+        assert!(
+            run("103,0,99", &[1])
+                == IntcodeError::ImmediateWrite {
+                    pc: 0,
+                    instruction: 103
+                }
+        );
+        assert!(
+            run("4,-3,99", &[])
+                == IntcodeError::NegativeAddress {
+                    pc: 0,
+                    instruction: 4,
+                    address: -3
+                }
+        );
+        assert!(
+            run("1105,1,-2", &[])
+                == IntcodeError::NegativeAddress {
+                    pc: -2,
+                    instruction: 0,
+                    address: -2
+                }
+        );
+        assert!(
+            run("3,0,3,0,99", &[1])
+                == IntcodeError::InputExhausted {
+                    pc: 2,
+                    instruction: 3
+                }
+        );
+    }
+
+    #[test]
+    fn outputs() {
+        // Prints a frame of three values for every input, until it reads 0.
+        let source = "
+            loop:   in @x
+                    jz @x, #done
+                    out @x
+                    add @x, #1, @y
+                    out @y
+                    mul @x, #2, @y
+                    out @y
+                    jz #0, #loop
+            done:   hlt
+            x:      data 0
+            y:      data 0
+        ";
+        let mut computer = IntCodeComputer::from_program(&assemble(source).unwrap());
+        computer.push_input(5);
+        computer.push_input(7);
+        let mut outputs = computer.outputs();
+        assert!(outputs.next_n() == Some([5, 6, 10]));
+        assert!(outputs.next() == Some(7));
+        assert!(outputs.next_n::<3>().is_none());
+        assert!(outputs.stop() == Some(Ok(RunState::NeedsInput)));
+        assert!(outputs.next().is_none());
+
+        computer.push_input(0);
+        let mut outputs = computer.outputs();
+        assert!(outputs.next().is_none());
+        assert!(outputs.stop() == Some(Ok(RunState::Halted)));
+
+        let mut computer = IntCodeComputer::from_program("104,1,104,2,42");
+        let mut outputs = computer.outputs();
+        assert!(outputs.by_ref().collect::<Vec<i64>>() == vec![1, 2]);
+        assert!(
+            outputs.stop()
+                == Some(Err(IntcodeError::InvalidOpcode {
+                    pc: 4,
+                    instruction: 42
+                }))
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let run = |program: &str, limits: Limits| {
+            let mut computer = IntCodeComputer::from_program(program);
+            computer.set_limits(limits);
+            let error = computer.run_to_halt(&[]).unwrap_err();
+            (error, computer)
+        };
+
+        let limits = Limits {
+            instructions: Some(10),
+            ..Default::default()
+        };
+        let (error, computer) = run("1101,1,1,7,1105,1,0,0", limits);
+        assert!(
+            error
+                == IntcodeError::InstructionLimit {
+                    pc: 0,
+                    instruction: 1101,
+                    limit: 10
+                }
+        );
+        assert!(computer.stats().instructions == 10 && computer.read(7) == 2);
+
+        let limits = Limits {
+            max_address: Some(4095),
+            ..Default::default()
+        };
+        let (error, computer) = run("1101,1,1,4096,99", limits);
+        assert!(
+            error
+                == IntcodeError::MemoryLimit {
+                    pc: 0,
+                    instruction: 1101,
+                    address: 4096
+                }
+        );
+        assert!(computer.read(4096) == 0);
+        let (_, computer) = run("21101,1,1,4095,109,1,1105,1,0", limits);
+        assert!(computer.relative_base() == 1 && computer.read(4095) == 2);
+        let (error, _) = run("1105,1,1000000000000", limits);
+        assert!(
+            error
+                == IntcodeError::MemoryLimit {
+                    pc: 1_000_000_000_000,
+                    instruction: 0,
+                    address: 1_000_000_000_000
+                }
+        );
+        assert!(error
+            .to_string()
+            .starts_with("reached address 1000000000000"));
+        // The add and jump at 23 are fused, and must stop at the pc too.
+        let program = format!("1105,1,23{},1101,1,1,33,1105,1,23", ",0".repeat(20));
+        for &fuse in &[true, false] {
+            let mut computer = IntCodeComputer::from_program(&program);
+            computer.set_fusion(fuse);
+            computer.set_limits(Limits {
+                max_address: Some(10),
+                ..Default::default()
+            });
+            assert!(
+                computer.run_to_halt(&[]).unwrap_err()
+                    == IntcodeError::MemoryLimit {
+                        pc: 23,
+                        instruction: 1101,
+                        address: 23
+                    }
+            );
+            assert!(computer.stats().instructions == 1);
+        }
+
+        let limits = Limits {
+            outputs: Some(3),
+            ..Default::default()
+        };
+        let (error, _) = run("104,1,1105,1,0", limits);
+        assert!(
+            error
+                == IntcodeError::OutputLimit {
+                    pc: 0,
+                    instruction: 104,
+                    limit: 3
+                }
+        );
+    }
+
+    #[test]
+    fn bounded() {
+        // Counts down from its input, printing each value.
+        let source = "
+                    in @n
+            loop:   out @n
+                    add @n, #-1, @n
+                    jnz @n, #loop
+                    hlt
+            n:      data 0
+        ";
+        let mut computer = IntCodeComputer::from_program(&assemble(source).unwrap());
+        let status = |stop, output: &[i64]| RunStatus {
+            stop,
+            output: output.to_vec(),
+        };
+        assert!(computer.step().unwrap() == status(Stop::NeedsInput, &[]));
+        computer.push_input(5);
+        assert!(computer.step().unwrap() == status(Stop::Paused, &[]));
+        assert!(computer.step().unwrap() == status(Stop::Paused, &[5]));
+        assert!(computer.pc() == 4);
+        assert!(computer.run_for(4).unwrap() == status(Stop::Paused, &[4]));
+        assert!(computer.pc() == 8);
+        assert!(computer.run_until_outputs(2).unwrap() == status(Stop::Paused, &[3, 2]));
+        assert!(computer.pc() == 4);
+        assert!(computer.run_until_pc(4).unwrap() == status(Stop::Paused, &[1]));
+        assert!(computer.run_for(0).unwrap() == status(Stop::Paused, &[]));
+        assert!(computer.run_until_outputs(3).unwrap() == status(Stop::Halted, &[]));
+        assert!(computer.step().unwrap() == status(Stop::Halted, &[]));
+        assert!(computer.stats().instructions == 17);
+    }
+
+    #[test]
+    fn trace() {
+        let buffer = SharedBuffer::default();
+        let mut computer = IntCodeComputer::from_program("109,5,203,2,204,2,99");
+        computer.trace_to(buffer.clone());
+        assert!(computer.run().unwrap() == RunState::NeedsInput);
+        computer.push_input(42);
+        assert!(computer.run().unwrap() == RunState::Output(42));
+        assert!(computer.run().unwrap() == RunState::Halted);
+        computer.finish_trace().unwrap();
+
+        let records = buffer.records();
+        assert!(records.len() == 4);
+        assert!(records[0].opcode == Opcode::AdjustRelativeBase && records[0].args == vec![5]);
+        assert!(
+            records[1]
+                == TraceRecord {
+                    pc: 2,
+                    opcode: Opcode::Input,
+                    modes: vec![Mode::Relative],
+                    args: vec![7],
+                    writes: vec![MemoryWrite {
+                        address: 7,
+                        old: 0,
+                        new: 42
+                    }],
+                    relative_base: 5,
+                    input: Some(42),
+                    output: None,
+                }
+        );
+        assert!(records[2].output == Some(42) && records[2].writes.is_empty());
+        assert!(records[3].opcode == Opcode::Halt);
+    }
+}
@@ -0,0 +1,165 @@
+// The conformance suite: the example programs from the puzzles, each in a
+// fixture file under `conformance/` that every way of running a program
+// must pass. A fixture looks like
+//
+//     # day05: prints whatever it reads.
+//     program: 3,0,4,0,99
+//
+//     input: 42
+//     output: 42
+//
+// Each `input` line starts a run of the program, which must halt having
+// printed what the `output` line after it says, or nothing if there isn't
+// one. A `memory` line gives the words memory must start with by then. A
+// fixture without any `input` lines is run once with none. Blank lines
+// and lines starting with `#` are ignored.
+
+use std::fs;
+use std::path::Path;
+
+use crate::computer::IntCodeComputer;
+use crate::fuzz::reference;
+use crate::program::Program;
+
+#[derive(Debug, Default)]
+struct Run {
+    input: Vec<i64>,
+    output: Vec<i64>,
+    memory: Option<Vec<i64>>,
+}
+
+#[derive(Debug)]
+struct Fixture {
+    name: String,
+    program: Vec<i64>,
+    runs: Vec<Run>,
+}
+
+fn parse_values(text: &str) -> Vec<i64> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse().unwrap())
+        .collect()
+}
+
+impl Fixture {
+    fn parse(name: &str, text: &str) -> Fixture {
+        let mut program = None;
+        let mut runs: Vec<Run> = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, values) = match line.find(':') {
+                Some(colon) => (&line[..colon], parse_values(&line[colon + 1..])),
+                None => panic!("{}: expected `key: values`, got {:?}", name, line),
+            };
+            if key == "input" || (key != "program" && runs.is_empty()) {
+                runs.push(Run::default());
+            }
+            let run = runs.last_mut();
+            match (key, run) {
+                ("program", _) => program = Some(values),
+                ("input", Some(run)) => run.input = values,
+                ("output", Some(run)) => run.output = values,
+                ("memory", Some(run)) => run.memory = Some(values),
+                _ => panic!("{}: unknown key {:?}", name, key),
+            }
+        }
+        if runs.is_empty() {
+            runs.push(Run::default());
+        }
+        Fixture {
+            name: name.to_string(),
+            program: program.unwrap_or_else(|| panic!("{}: no program", name)),
+            runs,
+        }
+    }
+}
+
+fn fixtures() -> Vec<Fixture> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("conformance");
+    let mut paths: Vec<_> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy();
+            Fixture::parse(&name, &fs::read_to_string(path).unwrap())
+        })
+        .collect()
+}
+
+// Every way a computer can run a program, by name.
+type Backend = (&'static str, fn(&mut IntCodeComputer));
+
+const BACKENDS: &[Backend] = &[
+    ("interpreter", |computer| {
+        computer.set_fusion(false);
+        #[cfg(feature = "jit")]
+        computer.set_jit(false);
+    }),
+    ("fused", |_computer| {
+        #[cfg(feature = "jit")]
+        _computer.set_jit(false);
+    }),
+    #[cfg(feature = "jit")]
+    ("jit", |_| {}),
+    ("history", |computer| computer.enable_history(1000)),
+];
+
+// Checks what a run printed and left in memory against `run`.
+fn check(fixture: &str, backend: &str, run: &Run, output: &[i64], memory: &[i64]) {
+    assert!(
+        output == &run.output[..],
+        "{} on {} with input {:?}: printed {:?}",
+        fixture,
+        backend,
+        run.input,
+        output
+    );
+    if let Some(expected) = &run.memory {
+        assert!(
+            memory.starts_with(expected),
+            "{} on {} with input {:?}: memory starts {:?}",
+            fixture,
+            backend,
+            run.input,
+            &memory[..expected.len().min(memory.len())]
+        );
+    }
+}
+
+#[test]
+fn test() {
+    let fixtures = fixtures();
+    assert!(fixtures.len() >= 18);
+    for fixture in &fixtures {
+        let program = Program::from_words(fixture.program.clone());
+        for run in &fixture.runs {
+            for &(backend, setup) in BACKENDS {
+                let mut computer = IntCodeComputer::new(&program);
+                setup(&mut computer);
+                let output = computer.run_to_halt(&run.input).unwrap_or_else(|error| {
+                    panic!("{} on {}: {}", fixture.name, backend, error);
+                });
+                let len = run.memory.as_ref().map_or(0, Vec::len) as i64;
+                let memory: Vec<i64> = (0..len).map(|address| computer.read(address)).collect();
+                check(&fixture.name, backend, run, &output, &memory);
+            }
+
+            let (output, error, memory) = reference(&fixture.program, &run.input);
+            assert!(
+                error.is_none(),
+                "{} on reference: {:?}",
+                fixture.name,
+                error
+            );
+            check(&fixture.name, "reference", run, &output, &memory);
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::computer::IntCodeComputer;
+use crate::error::{IntcodeError, Result};
+
+// An instruction beyond the ones Intcode defines, added to a computer with
+// `IntCodeComputer::register_opcode`. Its instruction word carries a mode
+// digit for each parameter, decoded like any other instruction's.
+pub trait CustomOpcode: Send + Sync {
+    // The last two digits of the instruction word. Can't be one of the
+    // standard opcodes.
+    fn code(&self) -> i64;
+
+    // At most three.
+    fn num_params(&self) -> usize;
+
+    // Whether parameter `i` is an address the instruction writes to, which
+    // makes immediate mode invalid for it.
+    fn writes(&self, i: usize) -> bool;
+
+    fn execute(&self, context: &mut OpcodeContext) -> Result<()>;
+}
+
+// What a custom instruction sees of the computer while it executes. The
+// computer carries on after it unless it calls `jump`.
+pub struct OpcodeContext<'a> {
+    pub(crate) computer: &'a mut IntCodeComputer,
+    pub(crate) opcode: &'a dyn CustomOpcode,
+    pub(crate) args: [i64; 3],
+    pub(crate) addresses: [i64; 3],
+    pub(crate) next: i64,
+    pub(crate) output: Option<i64>,
+}
+
+impl fmt::Debug for OpcodeContext<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OpcodeContext")
+            .field("pc", &self.pc())
+            .field("args", &self.args)
+            .field("addresses", &self.addresses)
+            .finish()
+    }
+}
+
+impl OpcodeContext<'_> {
+    pub fn pc(&self) -> i64 {
+        self.computer.pc()
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.computer.relative_base()
+    }
+
+    // The value of parameter `i`, read from memory unless it's immediate.
+    pub fn arg(&self, i: usize) -> i64 {
+        self.args[i]
+    }
+
+    // The address parameter `i` refers to in position or relative mode.
+    pub fn address(&self, i: usize) -> i64 {
+        self.addresses[i]
+    }
+
+    pub fn read(&self, address: i64) -> i64 {
+        self.computer.read(address)
+    }
+
+    // Writes `value` to the address of parameter `i`, which must be one the
+    // opcode says it writes. Limits and the self-modifying code policy
+    // apply as they do to `add`.
+    pub fn write(&mut self, i: usize, value: i64) -> Result<()> {
+        assert!(
+            self.opcode.writes(i),
+            "parameter {} of opcode {} isn't a write target",
+            i,
+            self.opcode.code()
+        );
+        self.computer.store(self.addresses[i], value)
+    }
+
+    // Continues at `address` instead of the next instruction.
+    pub fn jump(&mut self, address: i64) {
+        self.next = address;
+    }
+
+    // Prints `value`, so `run` returns `RunState::Output` after this
+    // instruction. Only one value can be printed per instruction.
+    pub fn output(&mut self, value: i64) -> Result<()> {
+        assert!(self.output.is_none(), "an instruction can only print once");
+        self.computer.count_output()?;
+        self.output = Some(value);
+        Ok(())
+    }
+}
+
+// The custom opcodes registered with a computer, by code. Forks share them.
+#[derive(Clone, Default)]
+pub(crate) struct CustomOpcodes {
+    opcodes: HashMap<i64, Arc<dyn CustomOpcode>>,
+}
+
+impl fmt::Debug for CustomOpcodes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut codes: Vec<&i64> = self.opcodes.keys().collect();
+        codes.sort();
+        f.debug_struct("CustomOpcodes")
+            .field("codes", &codes)
+            .finish()
+    }
+}
+
+impl CustomOpcodes {
+    pub(crate) fn insert(&mut self, opcode: Arc<dyn CustomOpcode>) {
+        self.opcodes.insert(opcode.code(), opcode);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.opcodes.is_empty()
+    }
+
+    // The opcode an instruction word that isn't a standard instruction
+    // stands for.
+    pub(crate) fn get(&self, pc: i64, word: i64) -> Result<Arc<dyn CustomOpcode>> {
+        match self.opcodes.get(&(word % 100)) {
+            Some(opcode) if word >= 0 => Ok(opcode.clone()),
+            _ => Err(IntcodeError::InvalidOpcode {
+                pc,
+                instruction: word,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::computer::{IntCodeComputer, RunState};
+    use crate::testutil::SharedBuffer;
+
+    struct Op<F> {
+        code: i64,
+        num_params: usize,
+        writes: Option<usize>,
+        f: F,
+    }
+
+    impl<F> CustomOpcode for Op<F>
+    where
+        F: Fn(&mut OpcodeContext) -> Result<()> + Send + Sync,
+    {
+        fn code(&self) -> i64 {
+            self.code
+        }
+
+        fn num_params(&self) -> usize {
+            self.num_params
+        }
+
+        fn writes(&self, i: usize) -> bool {
+            self.writes == Some(i)
+        }
+
+        fn execute(&self, context: &mut OpcodeContext) -> Result<()> {
+            (self.f)(context)
+        }
+    }
+
+    // What the debug print has logged, as (pc, value).
+    type Log = Arc<Mutex<Vec<(i64, i64)>>>;
+
+    // Registers `le` (10), a debug print (11), `jmp` (20) and a negating
+    // `out` (12).
+    fn setup(program: &str) -> (IntCodeComputer, Log) {
+        let mut computer = IntCodeComputer::from_program(program);
+        computer.register_opcode(Op {
+            code: 10,
+            num_params: 3,
+            writes: Some(2),
+            f: |context: &mut OpcodeContext| {
+                let value = (context.arg(0) <= context.arg(1)) as i64;
+                context.write(2, value)
+            },
+        });
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let printed = log.clone();
+        computer.register_opcode(Op {
+            code: 11,
+            num_params: 1,
+            writes: None,
+            f: move |context: &mut OpcodeContext| {
+                let entry = (context.pc(), context.arg(0));
+                printed.lock().unwrap().push(entry);
+                Ok(())
+            },
+        });
+        computer.register_opcode(Op {
+            code: 20,
+            num_params: 1,
+            writes: None,
+            f: |context: &mut OpcodeContext| {
+                context.jump(context.arg(0));
+                Ok(())
+            },
+        });
+        computer.register_opcode(Op {
+            code: 12,
+            num_params: 1,
+            writes: None,
+            f: |context: &mut OpcodeContext| context.output(-context.arg(0)),
+        });
+        (computer, log)
+    }
+
+    #[test]
+    fn test() {
+        // Prints whether 5 is at most its input, skipping the `out #7`.
+        let program = "3,100,110,5,100,101,11,101,4,101,120,14,104,7,99";
+        for &(input, expected) in [(3, 0), (5, 1)].iter() {
+            let (mut computer, log) = setup(program);
+            assert!(computer.run_to_halt(&[input]).unwrap() == vec![expected]);
+            assert!(*log.lock().unwrap() == vec![(6, expected)]);
+            assert!(computer.stats().instructions == 6);
+        }
+
+        // Traces and profiles leave custom instructions out.
+        let (mut computer, _) = setup(program);
+        let buffer = SharedBuffer::default();
+        computer.trace_to(buffer.clone());
+        computer.enable_profiling();
+        assert!(computer.run_to_halt(&[5]).unwrap() == vec![1]);
+        computer.finish_trace().unwrap();
+        let pcs: Vec<i64> = buffer.records().iter().map(|record| record.pc).collect();
+        assert!(pcs == vec![0, 8, 14]);
+        let profile = computer.profile().unwrap();
+        assert!(profile.instructions() == 3 && profile.pc_stats(2).is_none());
+        assert!(computer.stats().instructions == 6);
+
+        let (mut computer, log) = setup("109,100,211,1,12,101,99");
+        computer.write(101, 42);
+        assert!(computer.run().unwrap() == RunState::Output(-42));
+        assert!(computer.pc() == 6);
+        assert!(*log.lock().unwrap() == vec![(2, 42)]);
+
+        let (mut computer, _) = setup("11110,5,100,7");
+        assert!(
+            computer.run().unwrap_err()
+                == IntcodeError::ImmediateWrite {
+                    pc: 0,
+                    instruction: 11110
+                }
+        );
+        let (mut computer, _) = setup("104,1,13,99");
+        computer.run().unwrap();
+        assert!(
+            computer.run().unwrap_err()
+                == IntcodeError::InvalidOpcode {
+                    pc: 2,
+                    instruction: 13
+                }
+        );
+        let mut computer = IntCodeComputer::from_program(program);
+        assert!(
+            computer.run_to_halt(&[3]).unwrap_err()
+                == IntcodeError::InvalidOpcode {
+                    pc: 2,
+                    instruction: 110
+                }
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use crate::instruction::{Instruction, Mode};
+
+// Formats one operand: `@12` reads address 12, `#12` is the literal 12 and
+// `rb+12`/`rb-12` is relative to the relative base.
+pub fn format_param(mode: Mode, param: i64) -> String {
+    match mode {
+        Mode::Position => format!("@{}", param),
+        Mode::Immediate => format!("#{}", param),
+        Mode::Relative if param < 0 => format!("rb{}", param),
+        Mode::Relative => format!("rb+{}", param),
+    }
+}
+
+pub fn format_instruction(instruction: &Instruction, params: &[i64]) -> String {
+    let operands: Vec<String> = params
+        .iter()
+        .zip(instruction.modes.iter())
+        .map(|(param, mode)| format_param(*mode, *param))
+        .collect();
+    if operands.is_empty() {
+        instruction.opcode.mnemonic().to_string()
+    } else {
+        format!("{} {}", instruction.opcode.mnemonic(), operands.join(", "))
+    }
+}
+
+// Linear sweep over a program image, one line per instruction. Words that
+// don't decode, or whose parameters run off the end of the image, are
+// printed as data.
+pub fn disassemble(words: &[i64]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut address = 0;
+    while address < words.len() {
+        let decoded = Instruction::decode(address as i64, words[address])
+            .ok()
+            .filter(|instruction| address + (instruction.length() as usize) <= words.len());
+        match decoded {
+            Some(instruction) => {
+                let length = instruction.length() as usize;
+                let params = &words[address + 1..address + length];
+                lines.push(format!(
+                    "{:>6}  {}",
+                    address,
+                    format_instruction(&instruction, params)
+                ));
+                address += length;
+            }
+            None => {
+                lines.push(format!("{:>6}  data {}", address, words[address]));
+                address += 1;
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let words = [
+            109, 1, 204, -1, 1001, 100, 1, 100, 1108, 7, 8, 3, 99, -5, 6, 1, 2, 4,
+        ];
+        let expected = [
+            "     0  arb #1",
+            "     2  out rb-1",
+            "     4  add @100, #1, @100",
+            "     8  eq #7, #8, @3",
+            "    12  hlt",
+            "    13  data -5",
+            "    14  jz @1, @2",
+            "    17  data 4",
+        ];
+        assert!(disassemble(&words) == expected.join("\n"));
+    }
+}
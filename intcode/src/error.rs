@@ -0,0 +1,178 @@
+use std::error;
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, IntcodeError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntcodeError {
+    InvalidOpcode {
+        pc: i64,
+        instruction: i64,
+    },
+    InvalidMode {
+        pc: i64,
+        instruction: i64,
+        mode: i64,
+    },
+    ImmediateWrite {
+        pc: i64,
+        instruction: i64,
+    },
+    NegativeAddress {
+        pc: i64,
+        instruction: i64,
+        address: i64,
+    },
+    InputExhausted {
+        pc: i64,
+        instruction: i64,
+    },
+    SelfModifyingWrite {
+        pc: i64,
+        instruction: i64,
+        address: i64,
+    },
+    InstructionLimit {
+        pc: i64,
+        instruction: i64,
+        limit: u64,
+    },
+    MemoryLimit {
+        pc: i64,
+        instruction: i64,
+        address: i64,
+    },
+    OutputLimit {
+        pc: i64,
+        instruction: i64,
+        limit: u64,
+    },
+    // A result out of the range the arithmetic policy allows, or a value
+    // too wide for an i64 used as an address.
+    Overflow {
+        pc: i64,
+        instruction: i64,
+    },
+}
+
+impl IntcodeError {
+    pub fn pc(&self) -> i64 {
+        match *self {
+            IntcodeError::InvalidOpcode { pc, .. }
+            | IntcodeError::InvalidMode { pc, .. }
+            | IntcodeError::ImmediateWrite { pc, .. }
+            | IntcodeError::NegativeAddress { pc, .. }
+            | IntcodeError::InputExhausted { pc, .. }
+            | IntcodeError::SelfModifyingWrite { pc, .. }
+            | IntcodeError::InstructionLimit { pc, .. }
+            | IntcodeError::MemoryLimit { pc, .. }
+            | IntcodeError::OutputLimit { pc, .. }
+            | IntcodeError::Overflow { pc, .. } => pc,
+        }
+    }
+
+    pub fn instruction(&self) -> i64 {
+        match *self {
+            IntcodeError::InvalidOpcode { instruction, .. }
+            | IntcodeError::InvalidMode { instruction, .. }
+            | IntcodeError::ImmediateWrite { instruction, .. }
+            | IntcodeError::NegativeAddress { instruction, .. }
+            | IntcodeError::InputExhausted { instruction, .. }
+            | IntcodeError::SelfModifyingWrite { instruction, .. }
+            | IntcodeError::InstructionLimit { instruction, .. }
+            | IntcodeError::MemoryLimit { instruction, .. }
+            | IntcodeError::OutputLimit { instruction, .. }
+            | IntcodeError::Overflow { instruction, .. } => instruction,
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IntcodeError::InvalidOpcode { pc, instruction } => {
+                write!(f, "invalid opcode in {} at pc {}", instruction, pc)
+            }
+            IntcodeError::InvalidMode {
+                pc,
+                instruction,
+                mode,
+            } => write!(
+                f,
+                "invalid parameter mode {} in {} at pc {}",
+                mode, instruction, pc
+            ),
+            IntcodeError::ImmediateWrite { pc, instruction } => write!(
+                f,
+                "immediate mode write target in {} at pc {}",
+                instruction, pc
+            ),
+            IntcodeError::NegativeAddress {
+                pc,
+                instruction,
+                address,
+            } => write!(
+                f,
+                "negative address {} accessed by {} at pc {}",
+                address, instruction, pc
+            ),
+            IntcodeError::InputExhausted { pc, instruction } => write!(
+                f,
+                "input exhausted reading for {} at pc {}",
+                instruction, pc
+            ),
+            IntcodeError::SelfModifyingWrite {
+                pc,
+                instruction,
+                address,
+            } => write!(
+                f,
+                "write to executed address {} by {} at pc {}",
+                address, instruction, pc
+            ),
+            IntcodeError::InstructionLimit {
+                pc,
+                instruction,
+                limit,
+            } => write!(
+                f,
+                "instruction limit of {} reached before {} at pc {}",
+                limit, instruction, pc
+            ),
+            // Only reaching an address past the limit fails with the pc
+            // as the address, since a write can only fail at a smaller pc.
+            IntcodeError::MemoryLimit {
+                pc,
+                instruction,
+                address,
+            } if address == pc => write!(
+                f,
+                "reached address {} beyond the memory limit, holding {}",
+                pc, instruction
+            ),
+            IntcodeError::MemoryLimit {
+                pc,
+                instruction,
+                address,
+            } => write!(
+                f,
+                "write to address {} beyond the memory limit by {} at pc {}",
+                address, instruction, pc
+            ),
+            IntcodeError::OutputLimit {
+                pc,
+                instruction,
+                limit,
+            } => write!(
+                f,
+                "output limit of {} reached by {} at pc {}",
+                limit, instruction, pc
+            ),
+            IntcodeError::Overflow { pc, instruction } => {
+                write!(f, "integer overflow in {} at pc {}", instruction, pc)
+            }
+        }
+    }
+}
+
+impl error::Error for IntcodeError {}
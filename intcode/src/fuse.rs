@@ -0,0 +1,261 @@
+use crate::cfg::recover_cfg;
+use crate::instruction::{Instruction, Opcode};
+
+// Superinstructions: short runs of instructions that compiled Intcode
+// repeats over and over, run by the interpreter as a single step. Every
+// handler ends a basic block with a jump, and is one of
+//
+//     lt/eq, jump              a comparison and the branch on its result
+//     add/mul, jump            a counter bump, or a call storing its
+//                              return address before jumping
+//     arb, jump                a return: pop the frame, then jump back
+//     add/mul, lt/eq, jump     a loop counter, its test and the branch
+//     arb, lt/eq, jump         a routine's entry testing its argument
+//     add/mul, add/mul, jump   a call passing an argument
+//
+// Handlers are found once per program from its control-flow graph, and
+// carry their parameters with them so the interpreter never has to decode
+// or read them. Writing to any address a handler covers removes it, after
+// which that code is interpreted an instruction at a time as usual.
+
+// The most words a handler can cover: three four-word instructions.
+const MAX_SPAN: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Part {
+    pub(crate) pc: i64,
+    pub(crate) instruction: Instruction,
+    pub(crate) params: [i64; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Fused {
+    parts: [Part; 3],
+    len: usize,
+}
+
+impl Fused {
+    pub(crate) fn parts(&self) -> &[Part] {
+        &self.parts[..self.len]
+    }
+
+    pub(crate) fn start(&self) -> i64 {
+        self.parts[0].pc
+    }
+
+    pub(crate) fn end(&self) -> i64 {
+        let last = self.parts[self.len - 1];
+        last.pc + last.instruction.length()
+    }
+}
+
+// The fused handlers for a program, indexed by the address each starts at.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Fusion {
+    handlers: Vec<Option<Box<Fused>>>,
+    // Addresses inside some handler.
+    covered: Vec<bool>,
+}
+
+fn is_jump(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse)
+}
+
+fn is_arithmetic(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::Add | Opcode::Multiply)
+}
+
+fn is_comparison(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::LessThan | Opcode::Equals)
+}
+
+// How many instructions at the end of `opcodes` make up a handler, if any.
+fn pattern(opcodes: &[Opcode]) -> usize {
+    let n = opcodes.len();
+    if n < 2 || !is_jump(opcodes[n - 1]) {
+        return 0;
+    }
+    let before = opcodes[n - 2];
+    let triple = n >= 3
+        && match opcodes[n - 3] {
+            Opcode::Add | Opcode::Multiply => is_comparison(before) || is_arithmetic(before),
+            Opcode::AdjustRelativeBase => is_comparison(before),
+            _ => false,
+        };
+    if triple {
+        3
+    } else if is_comparison(before) || is_arithmetic(before) || before == Opcode::AdjustRelativeBase
+    {
+        2
+    } else {
+        0
+    }
+}
+
+impl Fusion {
+    pub(crate) fn new(words: &[i64]) -> Fusion {
+        let mut fusion = Fusion::default();
+        for block in recover_cfg(words).blocks.values() {
+            // These would only be removed again.
+            if block.self_modifying {
+                continue;
+            }
+            let opcodes: Vec<Opcode> = block
+                .instructions
+                .iter()
+                .map(|(_, instruction, _)| instruction.opcode)
+                .collect();
+            let len = pattern(&opcodes);
+            if len == 0 {
+                continue;
+            }
+            let mut parts = [Part {
+                pc: 0,
+                instruction: block.instructions[0].1,
+                params: [0; 3],
+            }; 3];
+            let tail = &block.instructions[block.instructions.len() - len..];
+            for (part, (pc, instruction, params)) in parts.iter_mut().zip(tail) {
+                part.pc = *pc;
+                part.instruction = *instruction;
+                part.params[..params.len()].copy_from_slice(params);
+            }
+            fusion.insert(Fused { parts, len });
+        }
+        fusion
+    }
+
+    fn insert(&mut self, fused: Fused) {
+        let start = fused.start() as usize;
+        let end = fused.end() as usize;
+        if start >= self.handlers.len() {
+            self.handlers.resize(start + 1, None);
+        }
+        self.handlers[start] = Some(Box::new(fused));
+        if end > self.covered.len() {
+            self.covered.resize(end, false);
+        }
+        for covered in &mut self.covered[start..end] {
+            *covered = true;
+        }
+    }
+
+    pub(crate) fn get(&self, pc: i64) -> Option<&Fused> {
+        if pc < 0 {
+            return None;
+        }
+        match self.handlers.get(pc as usize) {
+            Some(Some(fused)) => Some(fused),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn covers(&self, address: i64) -> bool {
+        address >= 0 && self.covered.get(address as usize) == Some(&true)
+    }
+
+    // Removes the handler covering `address`.
+    pub(crate) fn invalidate(&mut self, address: i64) {
+        if !self.covers(address) {
+            return;
+        }
+        let address = address as usize;
+        let first = address.saturating_sub(MAX_SPAN - 1);
+        for start in first..=address.min(self.handlers.len() - 1) {
+            let end = match &self.handlers[start] {
+                Some(fused) => fused.end() as usize,
+                None => continue,
+            };
+            if address < end {
+                self.handlers[start] = None;
+                for covered in &mut self.covered[start..end] {
+                    *covered = false;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::{assemble, assemble_words};
+    use crate::computer::{IntCodeComputer, SelfModifying};
+    use crate::program::Program;
+    use crate::testutil::{day, run};
+
+    // Runs `program` with and without fusion and checks they agree.
+    fn check(program: &Program, input: &[i64], policy: SelfModifying) {
+        let setup = |fuse: bool| {
+            let mut computer = IntCodeComputer::new(program);
+            computer.set_fusion(fuse);
+            computer.set_self_modifying(policy);
+            computer
+        };
+        assert!(run(&mut setup(true), input) == run(&mut setup(false), input));
+    }
+
+    #[test]
+    fn test() {
+        let source = "
+                    arb #100
+            loop:   add @n, #-1, @n
+                    lt #0, @n, @t
+                    jnz @t, #loop
+                    add #ret, #0, rb+0
+                    jz #0, #f
+            ret:    hlt
+            f:      arb #-1
+                    jz #0, rb+1
+            n:      data 3
+            t:      data 0
+        ";
+        let mut fusion = Fusion::new(&assemble_words(source).unwrap());
+        let starts: Vec<i64> = (0..40)
+            .filter_map(|pc| fusion.get(pc))
+            .map(|fused| fused.start())
+            .collect();
+        assert!(starts == vec![2, 13, 21]);
+        let fused = fusion.get(2).unwrap();
+        assert!(fused.parts().len() == 3 && fused.end() == 13);
+        assert!(fused.parts()[2].params == [27, 2, 0]);
+        assert!(fusion.covers(12) && !fusion.covers(0) && !fusion.covers(20));
+        fusion.invalidate(8);
+        assert!(fusion.get(2).is_none() && !fusion.covers(12));
+        assert!(fusion.get(13).is_some());
+
+        let program = Program::parse(&assemble(source).unwrap());
+        check(&program, &[], SelfModifying::Permissive);
+        let runs: &[(&str, &[i64])] = &[
+            ("day05", &[1]),
+            ("day05", &[5]),
+            ("day07", &[3, 7, 8, 9]),
+            ("day09", &[1]),
+            ("day09", &[2]),
+            ("day19", &[12, 20]),
+        ];
+        for &(name, input) in runs {
+            check(&day(name), input, SelfModifying::Permissive);
+        }
+
+        // The comparison sets the condition of the jump after it through the
+        // relative base, which the control-flow graph can't see. The stale
+        // handler would loop forever.
+        let source = "
+                    arb #jump+1
+            loop:   add @n, #-1, @n
+                    eq @n, #0, rb+0
+            jump:   jz #0, #loop
+                    out @n
+                    hlt
+            n:      data 5
+        ";
+        let program = Program::parse(&assemble(source).unwrap());
+        assert!(Fusion::new(program.words()).get(2).is_some());
+        check(&program, &[], SelfModifying::Permissive);
+        check(&program, &[], SelfModifying::Strict);
+        let mut computer = IntCodeComputer::new(&program);
+        assert!(computer.run_to_halt(&[]).unwrap() == vec![0]);
+        assert!(computer.stats().code_writes == 4);
+    }
+}
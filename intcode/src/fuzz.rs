@@ -0,0 +1,311 @@
+// Differential testing: random programs run every way the crate can run
+// them, and by a separate reference interpreter, must agree. proptest
+// shrinks any program they disagree on to a minimal one before reporting
+// it. Set PROPTEST_CASES to try more than the default 256.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::discriminant;
+
+use proptest::prelude::*;
+
+use crate::computer::{IntCodeComputer, Limits};
+use crate::error::IntcodeError;
+use crate::program::Program;
+use crate::testutil::run;
+
+// Bounds for every run, so random loops end and writes stay within the
+// memory an `Outcome` holds.
+const MAX_INSTRUCTIONS: u64 = 10_000;
+const MAX_ADDRESS: i64 = 4095;
+
+// A deliberately plain interpreter to check the others against, keeping
+// memory in a HashMap as the day09 solution first did. Returns what it
+// printed, the error it stopped with, if any, and the memory the others
+// report. It stops without an error on halting or running out of input.
+pub(crate) fn reference(
+    words: &[i64],
+    input: &[i64],
+) -> (Vec<i64>, Option<IntcodeError>, Vec<i64>) {
+    let mut memory: HashMap<i64, i64> = (0..).zip(words.iter().copied()).collect();
+    let read = |memory: &HashMap<i64, i64>, address: i64| *memory.get(&address).unwrap_or(&0);
+    let mut input = input.iter();
+    let mut output = Vec::new();
+    let mut pc = 0;
+    let mut relative_base: i64 = 0;
+    let mut executed = 0;
+    let error = loop {
+        if pc < 0 {
+            break Some(IntcodeError::NegativeAddress {
+                pc,
+                instruction: 0,
+                address: pc,
+            });
+        }
+        let word = read(&memory, pc);
+        if pc > MAX_ADDRESS {
+            break Some(IntcodeError::MemoryLimit {
+                pc,
+                instruction: word,
+                address: pc,
+            });
+        }
+        let invalid_opcode = IntcodeError::InvalidOpcode {
+            pc,
+            instruction: word,
+        };
+        let num_params = match word % 100 {
+            _ if word < 0 => break Some(invalid_opcode),
+            1 | 2 | 7 | 8 => 3,
+            5 | 6 => 2,
+            3 | 4 | 9 => 1,
+            99 => 0,
+            _ => break Some(invalid_opcode),
+        };
+        let modes = [word / 100 % 10, word / 1000 % 10, word / 10000 % 10];
+        let bad_mode = if word >= 100_000 {
+            Some(word / 100_000)
+        } else {
+            modes.iter().copied().find(|&mode| mode > 2)
+        };
+        if let Some(mode) = bad_mode {
+            break Some(IntcodeError::InvalidMode {
+                pc,
+                instruction: word,
+                mode,
+            });
+        }
+        let writes = matches!(word % 100, 1 | 2 | 3 | 7 | 8);
+        if writes && modes[num_params - 1] == 1 {
+            break Some(IntcodeError::ImmediateWrite {
+                pc,
+                instruction: word,
+            });
+        }
+
+        let mut args = [0; 3];
+        let mut addresses = [0; 3];
+        let mut negative = None;
+        for i in 0..num_params {
+            let param = read(&memory, pc + i as i64 + 1);
+            let address = match modes[i] {
+                0 => param,
+                1 => {
+                    args[i] = param;
+                    continue;
+                }
+                _ => param + relative_base,
+            };
+            if address < 0 {
+                negative = Some(address);
+                break;
+            }
+            args[i] = read(&memory, address);
+            addresses[i] = address;
+        }
+        if let Some(address) = negative {
+            break Some(IntcodeError::NegativeAddress {
+                pc,
+                instruction: word,
+                address,
+            });
+        }
+
+        let value = match word % 100 {
+            3 => match input.next() {
+                Some(value) => Some(*value),
+                None => break None,
+            },
+            _ => None,
+        };
+        if executed == MAX_INSTRUCTIONS {
+            break Some(IntcodeError::InstructionLimit {
+                pc,
+                instruction: word,
+                limit: MAX_INSTRUCTIONS,
+            });
+        }
+        executed += 1;
+        let overflow = IntcodeError::Overflow {
+            pc,
+            instruction: word,
+        };
+        let result = match word % 100 {
+            1 => args[0].checked_add(args[1]),
+            2 => args[0].checked_mul(args[1]),
+            3 => value,
+            7 => Some((args[0] < args[1]) as i64),
+            8 => Some((args[0] == args[1]) as i64),
+            _ => None,
+        };
+        if writes {
+            let address = addresses[num_params - 1];
+            let result = match result {
+                Some(result) => result,
+                None => break Some(overflow),
+            };
+            if address > MAX_ADDRESS {
+                break Some(IntcodeError::MemoryLimit {
+                    pc,
+                    instruction: word,
+                    address,
+                });
+            }
+            memory.insert(address, result);
+        }
+        match word % 100 {
+            4 => output.push(args[0]),
+            5 if args[0] != 0 => {
+                pc = args[1];
+                continue;
+            }
+            6 if args[0] == 0 => {
+                pc = args[1];
+                continue;
+            }
+            9 => match relative_base.checked_add(args[0]) {
+                Some(sum) => relative_base = sum,
+                None => break Some(overflow),
+            },
+            99 => break None,
+            _ => {}
+        }
+        pc += num_params as i64 + 1;
+    };
+    let memory = (0..=MAX_ADDRESS)
+        .map(|address| read(&memory, address))
+        .collect();
+    (output, error, memory)
+}
+
+// A generated program and the input to give it.
+#[derive(Clone)]
+struct Case {
+    words: Vec<i64>,
+    input: Vec<i64>,
+}
+
+// Printed so a failing case can be pasted into a file and run.
+impl fmt::Debug for Case {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let words: Vec<String> = self.words.iter().map(|word| word.to_string()).collect();
+        write!(f, "program {} input {:?}", words.join(","), self.input)
+    }
+}
+
+// A random instruction and its parameters. Most are valid, with the odd
+// unknown opcode, mode digit or immediate write thrown in. Parameters are
+// small, so they mostly refer to the program itself or the few words after
+// it.
+fn instruction() -> impl Strategy<Value = Vec<i64>> {
+    let opcode = prop_oneof![
+        60 => 1i64..=9,
+        2 => Just(99i64),
+        1 => 10i64..99,
+    ];
+    let mode = prop_oneof![80 => 0i64..=2, 1 => Just(3i64)];
+    let param = prop_oneof![40 => 0i64..48, 1 => -2i64..0];
+    let immediate_write = prop::bool::weighted(0.02);
+    (
+        opcode,
+        prop::array::uniform3(mode),
+        prop::array::uniform3(param),
+        immediate_write,
+    )
+        .prop_map(|(opcode, mut modes, params, immediate_write)| {
+            let num_params = match opcode {
+                1 | 2 | 7 | 8 => 3,
+                5 | 6 => 2,
+                3 | 4 | 9 => 1,
+                _ => 0,
+            };
+            let writes = matches!(opcode, 1 | 2 | 3 | 7 | 8);
+            if writes && modes[num_params - 1] == 1 && !immediate_write {
+                modes[num_params - 1] = 0;
+            }
+            let mut word = opcode;
+            for (i, mode) in modes[..num_params].iter().enumerate() {
+                word += mode * 10i64.pow(i as u32 + 2);
+            }
+            let mut words = vec![word];
+            words.extend(&params[..num_params]);
+            words
+        })
+}
+
+prop_compose! {
+    fn case()(
+        instructions in prop::collection::vec(instruction(), 1..24),
+        data in prop::collection::vec(-5i64..50, 0..8),
+        input in prop::collection::vec(-5i64..50, 0..6),
+    ) -> Case {
+        // Jumps with an immediate target go to the start of an
+        // instruction, so they make loops rather than land mid-instruction.
+        let mut starts = vec![0];
+        for instruction in &instructions {
+            starts.push(starts[starts.len() - 1] + instruction.len() as i64);
+        }
+        let mut words: Vec<i64> = Vec::new();
+        for mut instruction in instructions {
+            let jump = matches!(instruction[0] % 100, 5 | 6);
+            if jump && instruction[0] / 1000 % 10 == 1 {
+                instruction[2] = starts[instruction[2].rem_euclid(starts.len() as i64) as usize];
+            }
+            words.extend(instruction);
+        }
+        words.push(99);
+        words.extend(data);
+        Case { words, input }
+    }
+}
+
+// A computer for `case` that interprets it, fusing instructions if `fuse`
+// is set.
+fn computer(case: &Case, fuse: bool, limited: bool) -> IntCodeComputer {
+    let mut computer = IntCodeComputer::new(&Program::from_words(case.words.clone()));
+    computer.set_fusion(fuse);
+    #[cfg(feature = "jit")]
+    computer.set_jit(false);
+    if limited {
+        computer.set_limits(Limits {
+            instructions: Some(MAX_INSTRUCTIONS),
+            max_address: Some(MAX_ADDRESS),
+            outputs: None,
+        });
+    }
+    computer
+}
+
+proptest! {
+    #[test]
+    fn differential(case in case()) {
+        let (output, error, memory) = reference(&case.words, &case.input);
+        let interpreted = run(&mut computer(&case, false, true), &case.input);
+        prop_assert!(interpreted.output == output, "output {:?}", interpreted.output);
+        prop_assert!(
+            interpreted.error.as_ref().map(discriminant) == error.as_ref().map(discriminant),
+            "error {:?}, reference {:?}",
+            interpreted.error,
+            error
+        );
+        prop_assert!(interpreted.memory == memory, "memory differs from reference");
+        let fused = run(&mut computer(&case, true, true), &case.input);
+        prop_assert!(fused == interpreted, "fused run differs");
+
+        // Compiled code is never used with limits set, so it only gets
+        // programs the reference says stay within them.
+        #[cfg(feature = "jit")]
+        {
+            let limited = matches!(
+                error,
+                Some(IntcodeError::InstructionLimit { .. }) | Some(IntcodeError::MemoryLimit { .. })
+            );
+            if !limited {
+                let mut compiled = computer(&case, true, false);
+                compiled.set_jit(true);
+                let compiled = run(&mut compiled, &case.input);
+                prop_assert!(compiled == interpreted, "compiled run differs");
+            }
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::arith::Wide;
+use crate::computer::Stats;
+use crate::memory::Memory;
+
+// Full checkpoints are taken this many instructions apart. The undo log
+// only has to reach back past the latest one, so it's capped at twice this.
+pub(crate) const CHECKPOINT_INTERVAL: u64 = 4096;
+
+// What one executed instruction changed. `stats` and `outputs` are the
+// counts from before it ran, and `writes` holds every word it overwrote
+// as (address, old value, old wide value).
+#[derive(Debug, Clone)]
+pub(crate) struct Undo {
+    pub(crate) pc: i64,
+    pub(crate) relative_base: i64,
+    pub(crate) stats: Stats,
+    pub(crate) outputs: u64,
+    pub(crate) writes: Vec<(i64, i64, Option<Wide>)>,
+    pub(crate) input: Option<i64>,
+}
+
+// The machine state as it was after some number of instructions. Memory
+// pages are shared with the computer until one of them writes to them.
+#[derive(Debug, Clone)]
+pub(crate) struct Checkpoint {
+    pub(crate) memory: Memory,
+    pub(crate) counter: i64,
+    pub(crate) relative_base: i64,
+    pub(crate) halted: bool,
+    pub(crate) wide: HashMap<i64, Wide>,
+    pub(crate) outputs: u64,
+    pub(crate) stats: Stats,
+}
+
+// An undo log of recent instructions, and checkpoints going back at least
+// `limit` instructions to rebuild it from.
+#[derive(Debug, Clone)]
+pub(crate) struct History {
+    limit: u64,
+    // The instruction being executed, whose writes are added as it goes.
+    pub(crate) pending: Option<Undo>,
+    // Set while replaying from a checkpoint to search further back, so
+    // the undo log keeps everything replayed instead of just the latest.
+    pub(crate) rebuilding: bool,
+    undo: VecDeque<Undo>,
+    checkpoints: VecDeque<Checkpoint>,
+    // Inputs read since the oldest checkpoint, keyed by the number of
+    // instructions executed before the one that read each.
+    inputs: VecDeque<(u64, i64)>,
+}
+
+impl History {
+    pub(crate) fn new(limit: u64, checkpoint: Checkpoint) -> History {
+        History {
+            limit,
+            pending: None,
+            rebuilding: false,
+            undo: VecDeque::new(),
+            checkpoints: vec![checkpoint].into(),
+            inputs: VecDeque::new(),
+        }
+    }
+
+    // The earliest instruction count the computer can step back to, given
+    // that it's executed `now`.
+    pub(crate) fn start(&self, now: u64) -> u64 {
+        let logged = now - self.undo.len() as u64;
+        logged.min(self.checkpoints[0].stats.instructions)
+    }
+
+    // Whether the undo log reaches back to instruction count `target`.
+    pub(crate) fn logged(&self, now: u64, target: u64) -> bool {
+        now - self.undo.len() as u64 <= target
+    }
+
+    pub(crate) fn commit(&mut self, undo: Undo) {
+        if let Some(value) = undo.input {
+            self.inputs.push_back((undo.stats.instructions, value));
+        }
+        self.undo.push_back(undo);
+        if self.rebuilding {
+            return;
+        }
+        while self.undo.len() as u64 > self.limit.min(2 * CHECKPOINT_INTERVAL) {
+            self.undo.pop_front();
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Undo> {
+        let undo = self.undo.pop_back()?;
+        if self.inputs.back().map(|&(at, _)| at) == Some(undo.stats.instructions) {
+            self.inputs.pop_back();
+        }
+        Some(undo)
+    }
+
+    pub(crate) fn checkpoint_due(&self, now: u64) -> bool {
+        let last = &self.checkpoints[self.checkpoints.len() - 1];
+        now >= last.stats.instructions + CHECKPOINT_INTERVAL
+    }
+
+    // Adds a checkpoint, forgetting those no longer needed to reach back
+    // `limit` instructions.
+    pub(crate) fn push_checkpoint(&mut self, checkpoint: Checkpoint) {
+        let now = checkpoint.stats.instructions;
+        self.checkpoints.push_back(checkpoint);
+        while self.checkpoints.len() > 1
+            && self.checkpoints[1].stats.instructions + self.limit <= now
+        {
+            self.checkpoints.pop_front();
+        }
+        let oldest = self.checkpoints[0].stats.instructions;
+        while self.inputs.front().is_some_and(|&(at, _)| at < oldest) {
+            self.inputs.pop_front();
+        }
+    }
+
+    // The latest checkpoint taken at or before instruction count `target`.
+    pub(crate) fn checkpoint_before(&self, target: u64) -> Option<usize> {
+        self.checkpoints
+            .iter()
+            .rposition(|checkpoint| checkpoint.stats.instructions <= target)
+    }
+
+    // Forgets everything after checkpoint `index`, returning it along with
+    // the inputs read since, which will be read again.
+    pub(crate) fn rewind(&mut self, index: usize) -> (Checkpoint, VecDeque<i64>) {
+        self.checkpoints.truncate(index + 1);
+        let checkpoint = self.checkpoints[index].clone();
+        let count = checkpoint.stats.instructions;
+        while self
+            .undo
+            .back()
+            .is_some_and(|undo| undo.stats.instructions >= count)
+        {
+            self.undo.pop_back();
+        }
+        let split = self
+            .inputs
+            .iter()
+            .position(|&(at, _)| at >= count)
+            .unwrap_or(self.inputs.len());
+        let inputs = self.inputs.split_off(split);
+        (
+            checkpoint,
+            inputs.into_iter().map(|(_, value)| value).collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arith::Arithmetic;
+    use crate::asm::assemble;
+    use crate::computer::{IntCodeComputer, SelfModifying};
+    use crate::testutil::day;
+
+    // Everything stepping back should restore.
+    fn state(computer: &IntCodeComputer) -> (i64, i64, Stats, usize, Vec<i64>) {
+        (
+            computer.pc(),
+            computer.relative_base(),
+            computer.stats(),
+            computer.input_len(),
+            (0..1100).map(|address| computer.read(address)).collect(),
+        )
+    }
+
+    #[test]
+    fn test() {
+        // Sums its input into address 12 until it reads a zero, then prints
+        // the total.
+        let program = "3,13,1,12,13,12,1005,13,0,4,12,99,0,0";
+        let mut computer = IntCodeComputer::from_program(program);
+        computer.enable_history(100);
+        assert!(computer.run_to_halt(&[5, 7, 0]).unwrap() == vec![12]);
+        assert!(computer.step_back_to_write(12).unwrap());
+        assert!(computer.pc() == 2 && !computer.is_halted());
+        assert!(computer.stats().instructions == 7 && computer.input_len() == 0);
+        assert!(computer.step_back_to_write(12).unwrap());
+        assert!(computer.read(12) == 5 && computer.read(13) == 7);
+        assert!(computer.input_len() == 1);
+        assert!(computer.step_back(1).unwrap() == 1);
+        assert!(computer.pc() == 0 && computer.input_len() == 2);
+        assert!(computer.run_to_halt(&[]).unwrap() == vec![12]);
+        assert!(computer.step_back(100).unwrap() == 11);
+        let mut fresh = IntCodeComputer::from_program(program);
+        fresh.push_input(5);
+        fresh.push_input(7);
+        fresh.push_input(0);
+        assert!(state(&computer) == state(&fresh));
+        assert!(!computer.step_back_to_write(12).unwrap());
+        assert!(computer.step_back(1).unwrap() == 0);
+
+        // A failing instruction is undone, so it's the next to execute.
+        let mut computer = IntCodeComputer::from_program("3,0,99");
+        computer.set_self_modifying(SelfModifying::Strict);
+        computer.enable_history(100);
+        computer.push_input(7);
+        assert!(computer.run().unwrap_err().pc() == 0);
+        assert!(computer.stats().instructions == 0 && computer.input_len() == 1);
+
+        // Squares 2^32 in place.
+        let mut computer = IntCodeComputer::from_program("2,7,7,7,99,0,0,4294967296");
+        computer.set_arithmetic(Arithmetic::I128);
+        computer.enable_history(100);
+        computer.run_to_halt(&[]).unwrap();
+        assert!(computer.read_wide(7).to_i128() == Some(1 << 64));
+        assert!(computer.step_back(1).unwrap() == 1);
+        assert!(computer.read_wide(7).to_i128() == Some(1 << 64));
+        assert!(computer.step_back(1).unwrap() == 1);
+        assert!(computer.read(7) == 1 << 32 && computer.read_wide(7).to_i128() == Some(1 << 32));
+
+        // Stepping back past the undo log replays a wide `out`.
+        let mut computer = IntCodeComputer::from_program(
+            "2,15,15,15,4,15,1101,0,0,16,1101,0,0,16,99,4294967296,0",
+        );
+        computer.set_arithmetic(Arithmetic::I128);
+        computer.enable_history(1);
+        assert!(computer.run_to_halt_wide(&[]).unwrap() == vec![Wide::from(1i128 << 64)]);
+        assert!(computer.step_back(2).unwrap() == 2);
+        assert!(computer.pc() == 10 && computer.run_to_halt(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn checkpoints() {
+        // Sets `x` once, then counts down from 10000.
+        let source = "
+                    add #42, #0, @x
+            loop:   add @n, #-1, @n
+                    jnz @n, #loop
+                    hlt
+            x:      data 0
+            n:      data 10000
+        ";
+        let mut computer = IntCodeComputer::from_program(&assemble(source).unwrap());
+        computer.enable_history(100_000);
+        computer.run_to_halt(&[]).unwrap();
+        assert!(computer.step_back_to_write(12).unwrap());
+        assert!(computer.pc() == 0 && computer.read(13) == 10000);
+        assert!(!computer.step_back_to_write(12).unwrap());
+
+        // The shortest history still outlasts checkpoints.
+        let mut computer = IntCodeComputer::from_program(&assemble(source).unwrap());
+        computer.enable_history(0);
+        computer.run_to_halt(&[]).unwrap();
+        assert!(computer.step_back(3).unwrap() == 3);
+        assert!(computer.pc() == 4 && computer.read(13) == 1);
+        // Searching back past the undo log rebuilds it from a checkpoint
+        // once, rather than an instruction at a time.
+        let mut computer = IntCodeComputer::from_program(&assemble(source).unwrap());
+        computer.enable_history(1);
+        computer.run_to_halt(&[]).unwrap();
+        assert!(!computer.step_back_to_write(12).unwrap());
+        assert!(computer.step_back(1).unwrap() == 0 && computer.read(12) == 42);
+        computer.run_to_halt(&[]).unwrap();
+        assert!(computer.stats().instructions == 20_002 && computer.read(13) == 0);
+
+        // However long the program runs, the history reaches back at least
+        // `limit` instructions and not much more.
+        let program = day("day09");
+        let limit = 20_000;
+        let mut computer = IntCodeComputer::new(&program);
+        computer.enable_history(limit);
+        let output = computer.run_to_halt(&[2]).unwrap();
+        let end = computer.stats().instructions;
+        assert!(end > 10 * limit);
+        let mut at = end;
+        for &n in [1, 3, 5000, 9000, 4000].iter() {
+            assert!(computer.step_back(n).unwrap() == n);
+            at -= n;
+            let mut fresh = IntCodeComputer::new(&program);
+            fresh.push_input(2);
+            fresh.run_for(at).unwrap();
+            assert!(state(&computer) == state(&fresh));
+        }
+        let reach = end - at + computer.step_back(u64::MAX).unwrap();
+        assert!(reach >= limit && reach <= limit + CHECKPOINT_INTERVAL);
+        assert!(computer.run_to_halt(&[]).unwrap() == output);
+    }
+}
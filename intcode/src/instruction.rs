@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{IntcodeError, Result};
+
+// Opcodes serialize as their mnemonics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Opcode {
+    #[serde(rename = "add")]
+    Add,
+    #[serde(rename = "mul")]
+    Multiply,
+    #[serde(rename = "in")]
+    Input,
+    #[serde(rename = "out")]
+    Output,
+    #[serde(rename = "jnz")]
+    JumpIfTrue,
+    #[serde(rename = "jz")]
+    JumpIfFalse,
+    #[serde(rename = "lt")]
+    LessThan,
+    #[serde(rename = "eq")]
+    Equals,
+    #[serde(rename = "arb")]
+    AdjustRelativeBase,
+    #[serde(rename = "hlt")]
+    Halt,
+}
+
+impl Opcode {
+    pub fn from_code(code: i64) -> Option<Opcode> {
+        match code {
+            1 => Some(Opcode::Add),
+            2 => Some(Opcode::Multiply),
+            3 => Some(Opcode::Input),
+            4 => Some(Opcode::Output),
+            5 => Some(Opcode::JumpIfTrue),
+            6 => Some(Opcode::JumpIfFalse),
+            7 => Some(Opcode::LessThan),
+            8 => Some(Opcode::Equals),
+            9 => Some(Opcode::AdjustRelativeBase),
+            99 => Some(Opcode::Halt),
+            _ => None,
+        }
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Multiply => 2,
+            Opcode::Input => 3,
+            Opcode::Output => 4,
+            Opcode::JumpIfTrue => 5,
+            Opcode::JumpIfFalse => 6,
+            Opcode::LessThan => 7,
+            Opcode::Equals => 8,
+            Opcode::AdjustRelativeBase => 9,
+            Opcode::Halt => 99,
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+        match mnemonic {
+            "add" => Some(Opcode::Add),
+            "mul" => Some(Opcode::Multiply),
+            "in" => Some(Opcode::Input),
+            "out" => Some(Opcode::Output),
+            "jnz" => Some(Opcode::JumpIfTrue),
+            "jz" => Some(Opcode::JumpIfFalse),
+            "lt" => Some(Opcode::LessThan),
+            "eq" => Some(Opcode::Equals),
+            "arb" => Some(Opcode::AdjustRelativeBase),
+            "hlt" => Some(Opcode::Halt),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "add",
+            Opcode::Multiply => "mul",
+            Opcode::Input => "in",
+            Opcode::Output => "out",
+            Opcode::JumpIfTrue => "jnz",
+            Opcode::JumpIfFalse => "jz",
+            Opcode::LessThan => "lt",
+            Opcode::Equals => "eq",
+            Opcode::AdjustRelativeBase => "arb",
+            Opcode::Halt => "hlt",
+        }
+    }
+
+    pub fn num_params(self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Multiply | Opcode::LessThan | Opcode::Equals => 3,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => 2,
+            Opcode::Input | Opcode::Output | Opcode::AdjustRelativeBase => 1,
+            Opcode::Halt => 0,
+        }
+    }
+
+    // Whether the last parameter is an address the instruction writes to.
+    pub fn writes(self) -> bool {
+        matches!(
+            self,
+            Opcode::Add | Opcode::Multiply | Opcode::Input | Opcode::LessThan | Opcode::Equals
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    pub fn code(self) -> i64 {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub modes: [Mode; 3],
+}
+
+// The mode digits of an instruction word, one for each of up to three
+// parameters. Custom opcodes decode their modes with this too.
+pub(crate) fn decode_modes(pc: i64, word: i64) -> Result<[Mode; 3]> {
+    if word >= 100_000 {
+        return Err(IntcodeError::InvalidMode {
+            pc,
+            instruction: word,
+            mode: word / 100_000,
+        });
+    }
+    let mut modes = [Mode::Position; 3];
+    let mut digits = word / 100;
+    for mode in modes.iter_mut() {
+        *mode = match digits % 10 {
+            0 => Mode::Position,
+            1 => Mode::Immediate,
+            2 => Mode::Relative,
+            digit => {
+                return Err(IntcodeError::InvalidMode {
+                    pc,
+                    instruction: word,
+                    mode: digit,
+                })
+            }
+        };
+        digits /= 10;
+    }
+    Ok(modes)
+}
+
+impl Instruction {
+    // Splits `word` into an opcode (the last two decimal digits) and one mode
+    // digit per parameter. `pc` is only used to report errors.
+    pub fn decode(pc: i64, word: i64) -> Result<Instruction> {
+        let invalid_opcode = IntcodeError::InvalidOpcode {
+            pc,
+            instruction: word,
+        };
+        if word < 0 {
+            return Err(invalid_opcode);
+        }
+        let opcode = Opcode::from_code(word % 100).ok_or(invalid_opcode)?;
+        let modes = decode_modes(pc, word)?;
+        let num_params = opcode.num_params();
+        if opcode.writes() && modes[num_params - 1] == Mode::Immediate {
+            return Err(IntcodeError::ImmediateWrite {
+                pc,
+                instruction: word,
+            });
+        }
+        Ok(Instruction { opcode, modes })
+    }
+
+    pub fn encode(&self) -> i64 {
+        self.modes
+            .iter()
+            .rev()
+            .fold(0, |acc, mode| 10 * acc + mode.code())
+            * 100
+            + self.opcode.code()
+    }
+
+    pub fn length(&self) -> i64 {
+        self.opcode.num_params() as i64 + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        assert!(
+            Instruction::decode(0, 1002).unwrap()
+                == Instruction {
+                    opcode: Opcode::Multiply,
+                    modes: [Mode::Position, Mode::Immediate, Mode::Position],
+                }
+        );
+        assert!(
+            Instruction::decode(0, 21107).unwrap()
+                == Instruction {
+                    opcode: Opcode::LessThan,
+                    modes: [Mode::Immediate, Mode::Immediate, Mode::Relative],
+                }
+        );
+        for word in &[1002, 21107, 203, 99, 1105, 22201] {
+            assert!(Instruction::decode(0, *word).unwrap().encode() == *word);
+        }
+
+        assert!(Instruction::decode(0, 99).unwrap().length() == 1);
+        assert!(Instruction::decode(0, 203).unwrap().length() == 2);
+        assert!(Instruction::decode(0, 1105).unwrap().length() == 3);
+        assert!(Instruction::decode(0, 22201).unwrap().length() == 4);
+
+        assert!(
+            Instruction::decode(7, 10).unwrap_err()
+                == IntcodeError::InvalidOpcode {
+                    pc: 7,
+                    instruction: 10
+                }
+        );
+        assert!(
+            Instruction::decode(7, 1302).unwrap_err()
+                == IntcodeError::InvalidMode {
+                    pc: 7,
+                    instruction: 1302,
+                    mode: 3
+                }
+        );
+        assert!(
+            Instruction::decode(7, 100_001).unwrap_err()
+                == IntcodeError::InvalidMode {
+                    pc: 7,
+                    instruction: 100_001,
+                    mode: 1
+                }
+        );
+        assert!(
+            Instruction::decode(7, 10008).unwrap_err()
+                == IntcodeError::ImmediateWrite {
+                    pc: 7,
+                    instruction: 10008
+                }
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::mpsc::{Receiver, Sender, SyncSender};
+
+// Where a computer run with `IntCodeComputer::run_with` gets its input.
+// Returning `None` means there's no value to give it yet, and the run
+// stops with `RunState::NeedsInput`.
+pub trait IntcodeInput {
+    fn read_input(&mut self) -> Option<i64>;
+}
+
+// Where a computer run with `IntCodeComputer::run_with` puts its output.
+pub trait IntcodeOutput {
+    fn write_output(&mut self, value: i64);
+}
+
+// Values are read from the front, in the order they were pushed.
+impl IntcodeInput for VecDeque<i64> {
+    fn read_input(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+}
+
+// Blocks until a value arrives. A channel with no senders left has run dry.
+impl IntcodeInput for Receiver<i64> {
+    fn read_input(&mut self) -> Option<i64> {
+        self.recv().ok()
+    }
+}
+
+impl IntcodeOutput for Vec<i64> {
+    fn write_output(&mut self, value: i64) {
+        self.push(value);
+    }
+}
+
+// Pushed onto the back, so one computer's output can be another's input.
+impl IntcodeOutput for VecDeque<i64> {
+    fn write_output(&mut self, value: i64) {
+        self.push_back(value);
+    }
+}
+
+// Output sent once the receiver has hung up is dropped.
+impl IntcodeOutput for Sender<i64> {
+    fn write_output(&mut self, value: i64) {
+        let _ = self.send(value);
+    }
+}
+
+impl IntcodeOutput for SyncSender<i64> {
+    fn write_output(&mut self, value: i64) {
+        let _ = self.send(value);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InputIter<I> {
+    iter: I,
+}
+
+impl<I: Iterator<Item = i64>> IntcodeInput for InputIter<I> {
+    fn read_input(&mut self) -> Option<i64> {
+        self.iter.next()
+    }
+}
+
+// Input read from an iterator, which runs dry when the iterator ends.
+pub fn input_from_iter<I: IntoIterator<Item = i64>>(iter: I) -> InputIter<I::IntoIter> {
+    InputIter {
+        iter: iter.into_iter(),
+    }
+}
+
+pub struct InputFn<F> {
+    f: F,
+}
+
+impl<F> fmt::Debug for InputFn<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("InputFn").finish()
+    }
+}
+
+impl<F: FnMut() -> Option<i64>> IntcodeInput for InputFn<F> {
+    fn read_input(&mut self) -> Option<i64> {
+        (self.f)()
+    }
+}
+
+// Input produced by calling `f` whenever the computer wants a value.
+pub fn input_from_fn<F: FnMut() -> Option<i64>>(f: F) -> InputFn<F> {
+    InputFn { f }
+}
+
+pub struct OutputFn<F> {
+    f: F,
+}
+
+impl<F> fmt::Debug for OutputFn<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OutputFn").finish()
+    }
+}
+
+impl<F: FnMut(i64)> IntcodeOutput for OutputFn<F> {
+    fn write_output(&mut self, value: i64) {
+        (self.f)(value)
+    }
+}
+
+// Output passed to `f` as it's printed.
+pub fn output_to_fn<F: FnMut(i64)>(f: F) -> OutputFn<F> {
+    OutputFn { f }
+}
+
+// Output collected by anything that can be extended with values, like a
+// `HashSet`.
+pub struct OutputExtend<E> {
+    sink: E,
+}
+
+impl<E> fmt::Debug for OutputExtend<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OutputExtend").finish()
+    }
+}
+
+impl<E: Extend<i64>> IntcodeOutput for OutputExtend<E> {
+    fn write_output(&mut self, value: i64) {
+        self.sink.extend(Some(value));
+    }
+}
+
+impl<E> OutputExtend<E> {
+    pub fn into_inner(self) -> E {
+        self.sink
+    }
+}
+
+pub fn output_to_extend<E: Extend<i64>>(sink: E) -> OutputExtend<E> {
+    OutputExtend { sink }
+}
+
+// Output written to `writer` one value per line. Write errors are held on
+// to and reported by `into_inner`, like a trace's.
+pub struct OutputWriter<W> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W> fmt::Debug for OutputWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OutputWriter")
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<W: Write> IntcodeOutput for OutputWriter<W> {
+    fn write_output(&mut self, value: i64) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(error) = writeln!(self.writer, "{}", value) {
+            self.error = Some(error);
+        }
+    }
+}
+
+impl<W: Write> OutputWriter<W> {
+    // Flushes the writer and hands it back, or the first error hit while
+    // writing to it.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+pub fn output_to_writer<W: Write>(writer: W) -> OutputWriter<W> {
+    OutputWriter {
+        writer,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    use super::*;
+    use crate::computer::{IntCodeComputer, RunState};
+
+    #[test]
+    fn test() {
+        // Prints its first input, then the sum of the next two.
+        let program = "3,15,4,15,3,15,3,16,1,15,16,15,4,15,99,0,0";
+        let run = |input: &mut dyn IntcodeInput, output: &mut dyn IntcodeOutput| {
+            IntCodeComputer::from_program(program).run_with(input, output)
+        };
+
+        let mut input: VecDeque<i64> = vec![1, 2, 3].into_iter().collect();
+        let mut output = Vec::new();
+        assert!(run(&mut input, &mut output).unwrap() == RunState::Halted);
+        assert!(output == vec![1, 5] && input.is_empty());
+
+        let mut output = VecDeque::new();
+        let state = run(&mut input_from_iter(vec![7, 8]), &mut output).unwrap();
+        assert!(state == RunState::NeedsInput && output == vec![7]);
+
+        let mut next = 0;
+        let mut input = input_from_fn(|| {
+            next += 10;
+            Some(next)
+        });
+        let mut printed = Vec::new();
+        run(&mut input, &mut output_to_fn(|value| printed.push(value))).unwrap();
+        assert!(printed == vec![10, 50]);
+
+        let mut output = output_to_writer(Vec::new());
+        run(&mut input_from_iter(1..), &mut output).unwrap();
+        assert!(output.into_inner().unwrap() == b"1\n5\n");
+
+        let mut output = output_to_extend(HashSet::new());
+        run(&mut input_from_iter(vec![3, 1, 2]), &mut output).unwrap();
+        assert!(output.into_inner() == vec![3].into_iter().collect());
+
+        // Sums inputs, feeding a computer that doubles them, across threads.
+        let doubler = "3,11,1002,11,2,11,4,11,1105,1,0,0";
+        let (to_first, mut from_main) = channel();
+        let (mut to_second, mut from_first) = channel();
+        let (mut to_main, from_second) = channel();
+        let first = thread::spawn(move || {
+            IntCodeComputer::from_program(program).run_with(&mut from_main, &mut to_second)
+        });
+        let second = thread::spawn(move || {
+            IntCodeComputer::from_program(doubler).run_with(&mut from_first, &mut to_main)
+        });
+        for value in [4, 5, 6].iter() {
+            to_first.send(*value).unwrap();
+        }
+        assert!(first.join().unwrap().unwrap() == RunState::Halted);
+        // The doubler runs dry once the first computer hangs up.
+        assert!(second.join().unwrap().unwrap() == RunState::NeedsInput);
+        assert!(from_second.iter().collect::<Vec<i64>>() == vec![8, 22]);
+    }
+}
@@ -0,0 +1,715 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use std::sync::Arc;
+
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder, MemFlags, Signature, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::Context;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
+
+use crate::computer::IntCodeComputer;
+use crate::instruction::{Instruction, Mode, Opcode};
+use crate::program::Program;
+
+// Compiles straight-line runs of Intcode to native code. A compiled block
+// covers arithmetic, comparisons and relative base adjustments, and ends
+// with a jump or just before anything it can't handle: I/O, halts and
+// words that don't decode. Memory is still read and written through the
+// computer, so paging, the decode cache and self-modifying code detection
+// all behave exactly as they do in the interpreter.
+//
+// A block gives control back to the interpreter early whenever an
+// instruction would fail or writes to the block itself, leaving the
+// interpreter to re-run or carry on from that instruction.
+
+// Blocks are only compiled after being entered this many times, so code
+// that runs once, like day02's, is never compiled.
+pub(crate) const HOT_THRESHOLD: u32 = 8;
+
+// The longest block compiled, in instructions.
+const MAX_BLOCK: usize = 64;
+
+// Code that keeps getting rewritten isn't worth compiling. Once this many
+// versions of a block have been compiled, its address is left to the
+// interpreter.
+const MAX_VERSIONS: usize = 4;
+
+// The state a compiled block runs against. Its layout is shared with the
+// generated code.
+#[repr(C)]
+pub(crate) struct JitContext {
+    pub(crate) computer: *mut IntCodeComputer,
+    pub(crate) relative_base: i64,
+    // Instructions completed before the block returned.
+    pub(crate) completed: i64,
+    pub(crate) start: i64,
+    pub(crate) end: i64,
+}
+
+// What the write helper tells the generated code.
+const WRITE_DONE: i64 = 0;
+const WRITE_REFUSED: i64 = 1;
+const WRITE_TO_BLOCK: i64 = 2;
+
+extern "C" fn jit_read(context: *mut JitContext, address: i64) -> i64 {
+    let context = unsafe { &*context };
+    unsafe { &*context.computer }.read(address)
+}
+
+extern "C" fn jit_write(
+    context: *mut JitContext,
+    address: i64,
+    value: i64,
+    instruction_end: i64,
+) -> i64 {
+    let context = unsafe { &*context };
+    let computer = unsafe { &mut *context.computer };
+    // Earlier instructions of this run of the block count as executed even
+    // though the computer only hears about them once the block returns.
+    let executed = address >= context.start && address < instruction_end;
+    if !computer.checked_write(address, value, executed) {
+        WRITE_REFUSED
+    } else if address >= context.start && address < context.end {
+        WRITE_TO_BLOCK
+    } else {
+        WRITE_DONE
+    }
+}
+
+pub(crate) struct CompiledBlock {
+    pub(crate) start: i64,
+    // The words the block was compiled from. It's only valid while memory
+    // still holds them.
+    pub(crate) words: Vec<i64>,
+    pub(crate) instructions: usize,
+    pub(crate) function: extern "C" fn(*mut JitContext) -> i64,
+}
+
+impl fmt::Debug for CompiledBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompiledBlock")
+            .field("start", &self.start)
+            .field("words", &self.words)
+            .finish()
+    }
+}
+
+impl CompiledBlock {
+    pub(crate) fn matches<F: Fn(i64) -> i64>(&self, read: F) -> bool {
+        self.words
+            .iter()
+            .enumerate()
+            .all(|(offset, word)| read(self.start + offset as i64) == *word)
+    }
+}
+
+// Compiled code for one program, shared by every computer running it.
+pub(crate) struct Jit {
+    module: JITModule,
+    read: FuncId,
+    write: FuncId,
+    // Every version of the block at each address compiled so far.
+    blocks: HashMap<i64, Vec<Arc<CompiledBlock>>>,
+    heat: HashMap<i64, u32>,
+}
+
+impl fmt::Debug for Jit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Jit")
+            .field("blocks", &self.blocks.len())
+            .finish()
+    }
+}
+
+impl Jit {
+    pub(crate) fn new() -> Jit {
+        let mut flags = settings::builder();
+        flags.set("use_colocated_libcalls", "false").unwrap();
+        flags.set("is_pic", "false").unwrap();
+        flags.set("opt_level", "speed").unwrap();
+        let isa = cranelift_native::builder()
+            .unwrap()
+            .finish(settings::Flags::new(flags))
+            .unwrap();
+        let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+        builder.symbol("intcode_jit_read", jit_read as *const u8);
+        builder.symbol("intcode_jit_write", jit_write as *const u8);
+        let mut module = JITModule::new(builder);
+
+        let pointer = module.target_config().pointer_type();
+        let mut read = module.make_signature();
+        read.params.push(AbiParam::new(pointer));
+        read.params.push(AbiParam::new(types::I64));
+        read.returns.push(AbiParam::new(types::I64));
+        let mut write = module.make_signature();
+        write.params.push(AbiParam::new(pointer));
+        write.params.extend([AbiParam::new(types::I64); 3].iter());
+        write.returns.push(AbiParam::new(types::I64));
+        let read = module
+            .declare_function("intcode_jit_read", Linkage::Import, &read)
+            .unwrap();
+        let write = module
+            .declare_function("intcode_jit_write", Linkage::Import, &write)
+            .unwrap();
+
+        Jit {
+            module,
+            read,
+            write,
+            blocks: HashMap::new(),
+            heat: HashMap::new(),
+        }
+    }
+
+    // Finds a compiled block at `start` matching memory as seen through
+    // `read`. Blocks that aren't compiled yet are compiled once they're
+    // hot, or straight away if `force` is set. Returns `Err` with the
+    // word at `start` if the code there can't be compiled at all.
+    pub(crate) fn lookup<F: Fn(i64) -> i64>(
+        &mut self,
+        start: i64,
+        read: F,
+        force: bool,
+    ) -> Result<Option<Arc<CompiledBlock>>, i64> {
+        if let Some(blocks) = self.blocks.get(&start) {
+            if let Some(block) = blocks.iter().find(|block| block.matches(&read)) {
+                return Ok(Some(block.clone()));
+            }
+        }
+        let heat = self.heat.entry(start).or_insert(0);
+        *heat += 1;
+        if *heat < HOT_THRESHOLD && !force {
+            return Ok(None);
+        }
+        if self.blocks.get(&start).map_or(0, |blocks| blocks.len()) >= MAX_VERSIONS {
+            return Err(read(start));
+        }
+        let instructions = gather(start, &read);
+        if instructions.is_empty() {
+            return Err(read(start));
+        }
+        let block = Arc::new(self.compile(start, &instructions, &read));
+        self.blocks.entry(start).or_default().push(block.clone());
+        Ok(Some(block))
+    }
+
+    fn compile<F: Fn(i64) -> i64>(
+        &mut self,
+        start: i64,
+        instructions: &[(i64, Instruction)],
+        read: F,
+    ) -> CompiledBlock {
+        let pointer = self.module.target_config().pointer_type();
+        let mut signature: Signature = self.module.make_signature();
+        signature.params.push(AbiParam::new(pointer));
+        signature.returns.push(AbiParam::new(types::I64));
+
+        let mut context: Context = self.module.make_context();
+        context.func.signature = signature.clone();
+        let mut builder_context = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut context.func, &mut builder_context);
+        let read_helper = self.module.declare_func_in_func(self.read, builder.func);
+        let write_helper = self.module.declare_func_in_func(self.write, builder.func);
+
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+        let context_pointer = builder.block_params(entry)[0];
+        let relative_base = Variable::from_u32(0);
+        builder.declare_var(relative_base, types::I64);
+        let base = builder.ins().load(
+            types::I64,
+            MemFlags::trusted(),
+            context_pointer,
+            mem::offset_of!(JitContext, relative_base) as i32,
+        );
+        builder.def_var(relative_base, base);
+
+        let mut codegen = Codegen {
+            builder,
+            context: context_pointer,
+            relative_base,
+            read: read_helper,
+            write: write_helper,
+        };
+        let mut terminated = false;
+        for (completed, (pc, instruction)) in instructions.iter().enumerate() {
+            let params: Vec<i64> = (1..instruction.length()).map(|i| read(pc + i)).collect();
+            terminated = codegen.instruction(*pc, instruction, &params, completed as i64);
+        }
+        let (last, instruction) = instructions.last().unwrap();
+        let end = last + instruction.length();
+        if !terminated {
+            let end = codegen.builder.ins().iconst(types::I64, end);
+            codegen.exit(end, instructions.len() as i64);
+        }
+        codegen.builder.finalize();
+
+        let id = self.module.declare_anonymous_function(&signature).unwrap();
+        self.module.define_function(id, &mut context).unwrap();
+        self.module.clear_context(&mut context);
+        self.module.finalize_definitions().unwrap();
+        let code = self.module.get_finalized_function(id);
+        let function =
+            unsafe { mem::transmute::<*const u8, extern "C" fn(*mut JitContext) -> i64>(code) };
+
+        CompiledBlock {
+            start,
+            words: (start..end).map(read).collect(),
+            instructions: instructions.len(),
+            function,
+        }
+    }
+}
+
+// Decodes the instructions of the block starting at `start`.
+fn gather<F: Fn(i64) -> i64>(start: i64, read: F) -> Vec<(i64, Instruction)> {
+    let mut instructions = Vec::new();
+    let mut pc = start;
+    while instructions.len() < MAX_BLOCK {
+        let instruction = match Instruction::decode(pc, read(pc)) {
+            Ok(instruction) => instruction,
+            Err(_) => break,
+        };
+        let compilable = match instruction.opcode {
+            Opcode::Input | Opcode::Output | Opcode::Halt => false,
+            _ => (0..instruction.opcode.num_params())
+                .all(|i| instruction.modes[i] != Mode::Position || read(pc + 1 + i as i64) >= 0),
+        };
+        if !compilable {
+            break;
+        }
+        instructions.push((pc, instruction));
+        pc += instruction.length();
+        if matches!(instruction.opcode, Opcode::JumpIfTrue | Opcode::JumpIfFalse) {
+            break;
+        }
+    }
+    instructions
+}
+
+struct Codegen<'a> {
+    builder: FunctionBuilder<'a>,
+    context: Value,
+    relative_base: Variable,
+    read: cranelift_codegen::ir::FuncRef,
+    write: cranelift_codegen::ir::FuncRef,
+}
+
+impl<'a> Codegen<'a> {
+    // Emits one instruction, returning true if it ended the block.
+    fn instruction(
+        &mut self,
+        pc: i64,
+        instruction: &Instruction,
+        params: &[i64],
+        completed: i64,
+    ) -> bool {
+        let end = pc + instruction.length();
+        let opcode = instruction.opcode;
+        let reads = if opcode.writes() {
+            params.len() - 1
+        } else {
+            params.len()
+        };
+        let args: Vec<Value> = (0..reads)
+            .map(|i| self.operand(instruction.modes[i], params[i], pc, completed))
+            .collect();
+
+        let result = match opcode {
+            // Overflow leaves the block, so the interpreter can apply the
+            // computer's arithmetic policy.
+            Opcode::Add | Opcode::Multiply => {
+                let (result, overflow) = if opcode == Opcode::Add {
+                    self.builder.ins().sadd_overflow(args[0], args[1])
+                } else {
+                    self.builder.ins().smul_overflow(args[0], args[1])
+                };
+                self.exit_if(overflow, pc, completed);
+                result
+            }
+            Opcode::LessThan | Opcode::Equals => {
+                let condition = if opcode == Opcode::LessThan {
+                    IntCC::SignedLessThan
+                } else {
+                    IntCC::Equal
+                };
+                let flag = self.builder.ins().icmp(condition, args[0], args[1]);
+                self.builder.ins().uextend(types::I64, flag)
+            }
+            Opcode::AdjustRelativeBase => {
+                let base = self.builder.use_var(self.relative_base);
+                let (base, overflow) = self.builder.ins().sadd_overflow(base, args[0]);
+                self.exit_if(overflow, pc, completed);
+                self.builder.def_var(self.relative_base, base);
+                return false;
+            }
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let condition = if opcode == Opcode::JumpIfTrue {
+                    IntCC::NotEqual
+                } else {
+                    IntCC::Equal
+                };
+                let taken = self.builder.ins().icmp_imm(condition, args[0], 0);
+                let next = self.builder.ins().iconst(types::I64, end);
+                let target = self.builder.ins().select(taken, args[1], next);
+                self.exit(target, completed + 1);
+                return true;
+            }
+            Opcode::Input | Opcode::Output | Opcode::Halt => unreachable!(),
+        };
+
+        let last = params.len() - 1;
+        let address = self.address(instruction.modes[last], params[last], pc, completed);
+        let end_value = self.builder.ins().iconst(types::I64, end);
+        let call = self
+            .builder
+            .ins()
+            .call(self.write, &[self.context, address, result, end_value]);
+        let status = self.builder.inst_results(call)[0];
+        let refused = self
+            .builder
+            .ins()
+            .icmp_imm(IntCC::Equal, status, WRITE_REFUSED);
+        self.exit_if(refused, pc, completed);
+        let to_block = self
+            .builder
+            .ins()
+            .icmp_imm(IntCC::Equal, status, WRITE_TO_BLOCK);
+        self.exit_if(to_block, end, completed + 1);
+        false
+    }
+
+    fn operand(&mut self, mode: Mode, param: i64, pc: i64, completed: i64) -> Value {
+        if mode == Mode::Immediate {
+            return self.builder.ins().iconst(types::I64, param);
+        }
+        let address = self.address(mode, param, pc, completed);
+        let call = self.builder.ins().call(self.read, &[self.context, address]);
+        self.builder.inst_results(call)[0]
+    }
+
+    // Resolves a position or relative mode parameter to an address,
+    // leaving the block if it's negative so the interpreter reports it.
+    fn address(&mut self, mode: Mode, param: i64, pc: i64, completed: i64) -> Value {
+        let param = self.builder.ins().iconst(types::I64, param);
+        if mode == Mode::Position {
+            return param;
+        }
+        let base = self.builder.use_var(self.relative_base);
+        let address = self.builder.ins().iadd(base, param);
+        let negative = self
+            .builder
+            .ins()
+            .icmp_imm(IntCC::SignedLessThan, address, 0);
+        self.exit_if(negative, pc, completed);
+        address
+    }
+
+    fn exit_if(&mut self, condition: Value, pc: i64, completed: i64) {
+        let exit = self.builder.create_block();
+        let next = self.builder.create_block();
+        self.builder.set_cold_block(exit);
+        self.builder.ins().brif(condition, exit, &[], next, &[]);
+        self.builder.seal_block(exit);
+        self.builder.seal_block(next);
+        self.builder.switch_to_block(exit);
+        let pc = self.builder.ins().iconst(types::I64, pc);
+        self.exit(pc, completed);
+        self.builder.switch_to_block(next);
+    }
+
+    fn exit(&mut self, pc: Value, completed: i64) {
+        let base = self.builder.use_var(self.relative_base);
+        self.builder.ins().store(
+            MemFlags::trusted(),
+            base,
+            self.context,
+            mem::offset_of!(JitContext, relative_base) as i32,
+        );
+        let completed = self.builder.ins().iconst(types::I64, completed);
+        self.builder.ins().store(
+            MemFlags::trusted(),
+            completed,
+            self.context,
+            mem::offset_of!(JitContext, completed) as i32,
+        );
+        self.builder.ins().return_(&[pc]);
+    }
+}
+
+// What's needed to run a block, without holding on to it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BlockRef {
+    pub(crate) function: extern "C" fn(*mut JitContext) -> i64,
+    pub(crate) start: i64,
+    pub(crate) end: i64,
+    pub(crate) instructions: usize,
+    pub(crate) marked: bool,
+}
+
+impl BlockRef {
+    fn new(block: &CompiledBlock, marked: bool) -> BlockRef {
+        BlockRef {
+            function: block.function,
+            start: block.start,
+            end: block.start + block.words.len() as i64,
+            instructions: block.instructions,
+            marked,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    // Entered this many times without compiled code to run.
+    Cold(u32),
+    // A block, and whether every word in it has been marked executed.
+    Compiled(Arc<CompiledBlock>, bool),
+    // The code starting with this word can't be compiled.
+    Uncompilable(i64),
+}
+
+// A computer's view of the compiled code for its program, indexed by the
+// address each block starts at.
+#[derive(Debug, Clone)]
+pub(crate) struct JitState {
+    pub(crate) enabled: bool,
+    program: Program,
+    entries: Vec<Entry>,
+    // Addresses inside a block in `entries`. Blocks aren't checked against
+    // memory when they're run, so writing to one of these drops them all.
+    covered: Vec<bool>,
+}
+
+impl JitState {
+    pub(crate) fn new(program: &Program) -> JitState {
+        JitState {
+            enabled: true,
+            program: program.clone(),
+            entries: Vec::new(),
+            covered: Vec::new(),
+        }
+    }
+
+    // Returns compiled code to run at `pc`, if there is any. The program's
+    // shared code is consulted the first time an address is reached and
+    // again once it gets hot here. Code outside the program's image is
+    // always interpreted, so jumping far away doesn't grow `entries`.
+    pub(crate) fn block<F: Fn(i64) -> i64>(&mut self, pc: i64, read: F) -> Option<BlockRef> {
+        if pc < 0 || pc as usize >= self.program.words().len() {
+            return None;
+        }
+        let index = pc as usize;
+        if index >= self.entries.len() {
+            self.entries.resize(index + 1, Entry::Cold(0));
+        }
+        let heat = match &mut self.entries[index] {
+            Entry::Compiled(block, marked) => return Some(BlockRef::new(block, *marked)),
+            Entry::Uncompilable(word) if read(pc) == *word => return None,
+            Entry::Cold(heat) => {
+                *heat += 1;
+                *heat
+            }
+            Entry::Uncompilable(_) => 1,
+        };
+        if heat != 1 && heat != HOT_THRESHOLD {
+            self.entries[index] = Entry::Cold(heat);
+            return None;
+        }
+        let hot = heat >= HOT_THRESHOLD;
+        let found = match self.program.jit(hot) {
+            Some(jit) => jit.lock().unwrap().lookup(pc, &read, hot),
+            None => Ok(None),
+        };
+        match found {
+            Ok(Some(block)) => {
+                let end = block.start as usize + block.words.len();
+                if end > self.covered.len() {
+                    self.covered.resize(end, false);
+                }
+                for covered in &mut self.covered[block.start as usize..end] {
+                    *covered = true;
+                }
+                let found = BlockRef::new(&block, false);
+                self.entries[index] = Entry::Compiled(block, false);
+                Some(found)
+            }
+            Ok(None) => {
+                self.entries[index] = Entry::Cold(heat);
+                None
+            }
+            Err(word) => {
+                self.entries[index] = Entry::Uncompilable(word);
+                None
+            }
+        }
+    }
+
+    // Notes that the block at `start` has been marked executed in full.
+    pub(crate) fn set_marked(&mut self, start: i64) {
+        if let Some(Entry::Compiled(_, marked)) = self.entries.get_mut(start as usize) {
+            *marked = true;
+        }
+    }
+
+    // Called for every write to memory.
+    pub(crate) fn invalidate(&mut self, address: i64) {
+        if self.covered.get(address as usize) != Some(&true) {
+            return;
+        }
+        for entry in self.entries.iter_mut() {
+            if let Entry::Compiled(..) = entry {
+                *entry = Entry::Cold(0);
+            }
+        }
+        self.covered.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arith::Arithmetic;
+    use crate::asm::assemble;
+    use crate::computer::SelfModifying;
+    use crate::testutil::{day, run};
+
+    // Runs `program` interpreted and compiled and checks they agree. Later
+    // compiled runs reuse code compiled by earlier ones.
+    fn check(program: &Program, patches: &[(i64, i64)], input: &[i64], policy: SelfModifying) {
+        let setup = |jit: bool| {
+            let mut computer = IntCodeComputer::new(program);
+            computer.set_jit(jit);
+            computer.set_self_modifying(policy);
+            for &(address, value) in patches {
+                computer.write(address, value);
+            }
+            computer
+        };
+        let expected = run(&mut setup(false), input);
+        for _ in 0..3 {
+            assert!(run(&mut setup(true), input) == expected);
+        }
+    }
+
+    #[test]
+    fn days() {
+        let permissive = SelfModifying::Permissive;
+        let program = day("day02");
+        for &(noun, verb) in [(12, 2), (49, 67), (0, 0)].iter() {
+            check(&program, &[(1, noun), (2, verb)], &[], permissive);
+        }
+        let program = day("day05");
+        check(&program, &[], &[1], permissive);
+        check(&program, &[], &[5], permissive);
+        let program = day("day07");
+        for phase in 0..10 {
+            check(&program, &[], &[phase, 7, 8, 9], permissive);
+        }
+        let program = day("day09");
+        check(&program, &[], &[1], permissive);
+        check(&program, &[], &[2], permissive);
+        assert!(!program
+            .jit(false)
+            .unwrap()
+            .lock()
+            .unwrap()
+            .blocks
+            .is_empty());
+        check(&day("day13"), &[], &[], permissive);
+        check(&day("day17"), &[], &[], permissive);
+        let program = day("day19");
+        for x in 0..8 {
+            for y in 0..8 {
+                check(&program, &[], &[x * 3, y * 4], permissive);
+            }
+        }
+    }
+
+    #[test]
+    fn self_modifying() {
+        // Bumps the immediate in `patch` every time around the loop.
+        let source = "
+            loop:   add @patch+1, #1, @patch+1
+            patch:  add #0, @acc, @acc
+                    add @n, #-1, @n
+                    jnz @n, #loop
+                    out @acc
+                    hlt
+            n:      data 50
+            acc:    data 0
+        ";
+        let program = Program::parse(&assemble(source).unwrap());
+        check(&program, &[], &[], SelfModifying::Permissive);
+        check(&program, &[], &[], SelfModifying::Strict);
+
+        // Overwrites the next instruction in the block with a halt once the
+        // loop is hot.
+        let source = "
+            loop:   add @n, #-1, @n
+                    eq @n, #0, @stop
+                    mul @stop, #99, @next
+            next:   add #0, #0, @0
+                    jnz #1, #loop
+            n:      data 30
+            stop:   data 0
+        ";
+        let program = Program::parse(&assemble(source).unwrap());
+        check(&program, &[], &[], SelfModifying::Permissive);
+    }
+
+    #[test]
+    fn errors() {
+        // Walks the relative base down until it reads a negative address.
+        let source = "
+                    arb #20
+            loop:   arb #-1
+                    add rb+0, #1, @x
+                    jnz #1, #loop
+            x:      data 0
+        ";
+        let program = Program::parse(&assemble(source).unwrap());
+        check(&program, &[], &[], SelfModifying::Permissive);
+
+        // Counts down and then jumps to a negative address.
+        let source = "
+            loop:   add @n, #-1, @n
+                    jnz @n, #loop
+                    jz #0, #-5
+            n:      data 20
+        ";
+        let program = Program::parse(&assemble(source).unwrap());
+        check(&program, &[], &[], SelfModifying::Permissive);
+
+        // Jumps far past the end of the program, to an invalid opcode.
+        let program = Program::parse("1105,1,1000000000000");
+        check(&program, &[], &[], SelfModifying::Permissive);
+
+        // Triples a value in a hot loop until it overflows, which is an
+        // error unless arithmetic wraps.
+        let source = "
+            loop:   mul @x, #3, @x
+                    add @n, #-1, @n
+                    jnz @n, #loop
+                    out @x
+                    hlt
+            n:      data 100
+            x:      data 1
+        ";
+        let program = Program::parse(&assemble(source).unwrap());
+        check(&program, &[], &[], SelfModifying::Permissive);
+        let setup = |jit: bool| {
+            let mut computer = IntCodeComputer::new(&program);
+            computer.set_jit(jit);
+            computer.set_arithmetic(Arithmetic::Wrapping);
+            computer
+        };
+        assert!(run(&mut setup(true), &[]) == run(&mut setup(false), &[]));
+    }
+}
@@ -0,0 +1,41 @@
+mod arith;
+mod asm;
+mod cfg;
+mod computer;
+#[cfg(test)]
+mod conformance;
+mod custom;
+mod disasm;
+mod error;
+mod fuse;
+#[cfg(test)]
+mod fuzz;
+mod history;
+mod instruction;
+mod io;
+#[cfg(feature = "jit")]
+mod jit;
+mod memory;
+mod profile;
+mod program;
+#[cfg(test)]
+mod testutil;
+mod trace;
+
+pub use arith::{Arithmetic, Wide};
+pub use asm::{assemble, assemble_words, AsmError};
+pub use cfg::{recover_cfg, BasicBlock, Cfg, Edge, EdgeKind};
+pub use computer::{
+    IntCodeComputer, Limits, Outputs, RunState, RunStatus, SelfModifying, Stats, Stop,
+};
+pub use custom::{CustomOpcode, OpcodeContext};
+pub use disasm::{disassemble, format_instruction, format_param};
+pub use error::{IntcodeError, Result};
+pub use instruction::{Instruction, Mode, Opcode};
+pub use io::{
+    input_from_fn, input_from_iter, output_to_extend, output_to_fn, output_to_writer, InputFn,
+    InputIter, IntcodeInput, IntcodeOutput, OutputExtend, OutputFn, OutputWriter,
+};
+pub use profile::{PcStats, Profile};
+pub use program::{ParseError, Program};
+pub use trace::{diff_traces, Divergence, MemoryWrite, TraceRecord};
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const PAGE_SIZE: usize = 1024;
+
+// Pages below this index are kept in a Vec, which stays small enough to
+// grow on demand. Pages past it are kept in a map, so a write to a far
+// address costs one page rather than every page before it.
+const DENSE_PAGES: usize = 4096;
+
+type Page = [i64; PAGE_SIZE];
+
+// Growable VM memory split into fixed-size pages. Pages are shared
+// between clones until one of them writes to it, so a VM started from a
+// parsed `Program` only copies the pages it actually modifies.
+#[derive(Debug, Clone, Default)]
+pub struct Memory {
+    pages: Vec<Option<Arc<Page>>>,
+    far: HashMap<usize, Arc<Page>>,
+}
+
+impl Memory {
+    pub fn from_words(words: &[i64]) -> Memory {
+        let pages = words
+            .chunks(PAGE_SIZE)
+            .map(|chunk| {
+                let mut page = [0; PAGE_SIZE];
+                page[..chunk.len()].copy_from_slice(chunk);
+                Some(Arc::new(page))
+            })
+            .collect();
+        Memory {
+            pages,
+            far: HashMap::new(),
+        }
+    }
+
+    pub fn read(&self, address: usize) -> i64 {
+        let index = address / PAGE_SIZE;
+        match self.pages.get(index) {
+            Some(Some(page)) => page[address % PAGE_SIZE],
+            Some(None) => 0,
+            None => self.read_far(index, address),
+        }
+    }
+
+    #[cold]
+    fn read_far(&self, index: usize, address: usize) -> i64 {
+        match self.far.get(&index) {
+            Some(page) => page[address % PAGE_SIZE],
+            None => 0,
+        }
+    }
+
+    pub fn write(&mut self, address: usize, value: i64) {
+        let index = address / PAGE_SIZE;
+        let page = if index < self.pages.len().max(DENSE_PAGES) {
+            if index >= self.pages.len() {
+                self.pages.resize(index + 1, None);
+            }
+            self.pages[index].get_or_insert_with(|| Arc::new([0; PAGE_SIZE]))
+        } else {
+            self.far
+                .entry(index)
+                .or_insert_with(|| Arc::new([0; PAGE_SIZE]))
+        };
+        Arc::make_mut(page)[address % PAGE_SIZE] = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let mut memory = Memory::from_words(&[1, 2, 3]);
+        assert!(memory.read(2) == 3);
+        assert!(memory.read(3) == 0);
+        assert!(memory.read(10 * PAGE_SIZE) == 0);
+
+        let copy = memory.clone();
+        assert!(Arc::ptr_eq(
+            copy.pages[0].as_ref().unwrap(),
+            memory.pages[0].as_ref().unwrap()
+        ));
+        memory.write(0, 7);
+        memory.write(5 * PAGE_SIZE + 1, 9);
+        assert!(memory.read(0) == 7);
+        assert!(memory.read(5 * PAGE_SIZE + 1) == 9);
+        assert!(memory.pages[3].is_none());
+        assert!(copy.read(0) == 1);
+        assert!(copy.read(5 * PAGE_SIZE + 1) == 0);
+
+        // Far writes don't grow the pages before them.
+        let far = 1 << 50;
+        memory.write(far, 11);
+        assert!(memory.read(far) == 11 && memory.read(far + 1) == 0);
+        assert!(memory.pages.len() <= DENSE_PAGES);
+        assert!(memory.read(usize::MAX) == 0);
+    }
+}
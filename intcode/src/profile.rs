@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use crate::instruction::{Instruction, Opcode};
+
+// Execution counts gathered while a computer runs with profiling on.
+//
+// Intcode has no call instruction, but compiled programs bump the relative
+// base on entry to a routine and drop it again on the way out, so a rise in
+// the relative base is treated as a call into a frame named after the pc
+// that raised it and a fall as a return.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    instructions: u64,
+    pcs: HashMap<i64, PcStats>,
+    opcodes: HashMap<Opcode, u64>,
+    // Frames form a tree: each is its parent plus the pc that entered it.
+    // Frame 0 is the root and has no entry in `frames`.
+    frames: Vec<(usize, i64)>,
+    children: HashMap<(usize, i64), usize>,
+    frame_counts: Vec<u64>,
+    stack: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PcStats {
+    pub count: u64,
+    pub taken: u64,
+    pub not_taken: u64,
+    // The instruction as first seen at this pc.
+    pub text: String,
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile {
+            frame_counts: vec![0],
+            ..Default::default()
+        }
+    }
+
+    // Records one executed instruction. `text` is only called the first
+    // time `pc` is seen. `old_base` and `new_base` are the relative base
+    // before and after it ran.
+    pub(crate) fn record<F: FnOnce() -> String>(
+        &mut self,
+        pc: i64,
+        instruction: &Instruction,
+        condition: i64,
+        old_base: i64,
+        new_base: i64,
+        text: F,
+    ) {
+        self.instructions += 1;
+        *self.opcodes.entry(instruction.opcode).or_insert(0) += 1;
+        let stats = self.pcs.entry(pc).or_insert_with(|| PcStats {
+            count: 0,
+            taken: 0,
+            not_taken: 0,
+            text: text(),
+        });
+        stats.count += 1;
+        let taken = match instruction.opcode {
+            Opcode::JumpIfTrue => Some(condition != 0),
+            Opcode::JumpIfFalse => Some(condition == 0),
+            _ => None,
+        };
+        match taken {
+            Some(true) => stats.taken += 1,
+            Some(false) => stats.not_taken += 1,
+            None => {}
+        }
+
+        let frame = self.current_frame();
+        self.frame_counts[frame] += 1;
+        if new_base > old_base {
+            let frames = &mut self.frames;
+            let frame_counts = &mut self.frame_counts;
+            let child = *self.children.entry((frame, pc)).or_insert_with(|| {
+                frames.push((frame, pc));
+                frame_counts.push(0);
+                frames.len()
+            });
+            self.stack.push(child);
+        } else if new_base < old_base {
+            self.stack.pop();
+        }
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn pc_stats(&self, pc: i64) -> Option<&PcStats> {
+        self.pcs.get(&pc)
+    }
+
+    pub fn opcode_count(&self, opcode: Opcode) -> u64 {
+        self.opcodes.get(&opcode).copied().unwrap_or(0)
+    }
+
+    // A plain-text report of the hottest pcs, opcodes and branches. Only
+    // the first `limit` pcs and branches are listed.
+    pub fn report(&self, limit: usize) -> String {
+        let mut lines = vec![format!("instructions executed: {}", self.instructions)];
+
+        lines.push(String::new());
+        lines.push("hottest pcs:".to_string());
+        lines.push(format!(
+            "{:>12} {:>7} {:>6}  instruction",
+            "count", "%", "pc"
+        ));
+        let mut pcs: Vec<(&i64, &PcStats)> = self.pcs.iter().collect();
+        pcs.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        for (pc, stats) in pcs.iter().take(limit) {
+            lines.push(format!(
+                "{:>12} {:>6.2}% {:>6}  {}",
+                stats.count,
+                self.percent(stats.count),
+                pc,
+                stats.text
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push("opcodes:".to_string());
+        let mut opcodes: Vec<(&Opcode, &u64)> = self.opcodes.iter().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.code().cmp(&b.0.code())));
+        for (opcode, count) in opcodes {
+            lines.push(format!(
+                "{:>12} {:>6.2}%  {}",
+                count,
+                self.percent(*count),
+                opcode.mnemonic()
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push("branches:".to_string());
+        lines.push(format!(
+            "{:>12} {:>12} {:>6}  instruction",
+            "taken", "not taken", "pc"
+        ));
+        let mut branches: Vec<&(&i64, &PcStats)> = pcs
+            .iter()
+            .filter(|(_, stats)| stats.taken + stats.not_taken > 0)
+            .collect();
+        branches.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        for (pc, stats) in branches.iter().take(limit) {
+            lines.push(format!(
+                "{:>12} {:>12} {:>6}  {}",
+                stats.taken, stats.not_taken, pc, stats.text
+            ));
+        }
+
+        lines.join("\n")
+    }
+
+    // Instruction counts per inferred call stack in the folded format read
+    // by flamegraph tools, one `root;pc_12;pc_340 count` line per stack.
+    pub fn folded_stacks(&self) -> String {
+        let mut lines: Vec<String> = (0..self.frame_counts.len())
+            .filter(|&frame| self.frame_counts[frame] > 0)
+            .map(|frame| format!("{} {}", self.frame_path(frame), self.frame_counts[frame]))
+            .collect();
+        lines.sort();
+        lines.join("\n")
+    }
+
+    fn current_frame(&self) -> usize {
+        self.stack.last().copied().unwrap_or(0)
+    }
+
+    fn frame_path(&self, mut frame: usize) -> String {
+        let mut names = Vec::new();
+        while frame != 0 {
+            let (parent, pc) = self.frames[frame - 1];
+            names.push(format!("pc_{}", pc));
+            frame = parent;
+        }
+        names.push("root".to_string());
+        names.reverse();
+        names.join(";")
+    }
+
+    fn percent(&self, count: u64) -> f64 {
+        100.0 * count as f64 / self.instructions.max(1) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::computer::IntCodeComputer;
+
+    #[test]
+    fn test() {
+        let source = "
+                    arb #100
+            loop:   arb #2
+                    arb #-2
+                    add @n, #-1, @n
+                    jnz @n, #loop
+                    hlt
+            n:      data 3
+        ";
+        let mut computer = IntCodeComputer::from_program(&assemble(source).unwrap());
+        computer.enable_profiling();
+        computer.run_to_halt(&[]).unwrap();
+        let profile = computer.profile().unwrap();
+
+        assert!(profile.instructions() == 14);
+        assert!(profile.opcode_count(Opcode::AdjustRelativeBase) == 7);
+        assert!(profile.opcode_count(Opcode::Multiply) == 0);
+        let branch = profile.pc_stats(10).unwrap();
+        assert!((branch.count, branch.taken, branch.not_taken) == (3, 2, 1));
+        assert!(branch.text == "jnz @14, #2");
+        assert!(profile.folded_stacks() == "root 1\nroot;pc_0 10\nroot;pc_0;pc_2 3");
+
+        let report = profile.report(2);
+        assert!(report.starts_with("instructions executed: 14\n"));
+        assert!(report.contains("           3  21.43%      2  arb #2\n"));
+        assert!(report.contains("           7  50.00%  arb\n"));
+        assert!(report.ends_with("           2            1     10  jnz @14, #2"));
+    }
+}
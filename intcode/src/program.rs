@@ -0,0 +1,226 @@
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+#[cfg(feature = "jit")]
+use std::sync::Mutex;
+use std::sync::{Arc, OnceLock};
+
+use crate::fuse::Fusion;
+#[cfg(feature = "jit")]
+use crate::jit::Jit;
+use crate::memory::Memory;
+
+// A parsed Intcode program. Cloning a `Program` or starting a VM from it
+// shares the parsed image instead of copying it.
+#[derive(Debug, Clone)]
+pub struct Program {
+    image: Arc<Image>,
+}
+
+#[derive(Debug)]
+struct Image {
+    words: Vec<i64>,
+    memory: Memory,
+    fusion: OnceLock<Arc<Fusion>>,
+    // Compiled code, created the first time a computer asks for it.
+    #[cfg(feature = "jit")]
+    jit: OnceLock<Mutex<Jit>>,
+}
+
+// A program that couldn't be loaded. `index` is the position of the bad
+// value in the program, and `line` and `column` count from 1, in
+// characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub index: usize,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {} (value {})",
+            self.line, self.column, self.message, self.index
+        )
+    }
+}
+
+impl error::Error for ParseError {}
+
+// Programs are comma-separated integers. Whitespace around values is
+// ignored, a line break separates values like a comma does, a trailing
+// comma is allowed, and `#` starts a comment running to the end of the
+// line, so fixtures can be written out an instruction per line:
+//
+//     3,9,        # in @9
+//     4,9,        # out @9
+//     99
+impl FromStr for Program {
+    type Err = ParseError;
+
+    fn from_str(source: &str) -> Result<Program, ParseError> {
+        let mut words = Vec::new();
+        // Whether the last value has been followed by a separator.
+        let mut separated = true;
+        for (number, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap();
+            let chars: Vec<char> = line.chars().collect();
+            let error = |column: usize, index: usize, message: String| ParseError {
+                index,
+                line: number + 1,
+                column: column + 1,
+                message,
+            };
+            let mut pos = 0;
+            while pos < chars.len() {
+                let c = chars[pos];
+                if c.is_whitespace() {
+                    pos += 1;
+                } else if c == ',' {
+                    if separated {
+                        return Err(error(pos, words.len(), "missing value".to_string()));
+                    }
+                    separated = true;
+                    pos += 1;
+                } else {
+                    let start = pos;
+                    while pos < chars.len() && !chars[pos].is_whitespace() && chars[pos] != ',' {
+                        pos += 1;
+                    }
+                    let token: String = chars[start..pos].iter().collect();
+                    if !separated {
+                        let message = format!("expected `,` before `{}`", token);
+                        return Err(error(start, words.len(), message));
+                    }
+                    match token.parse() {
+                        Ok(word) => words.push(word),
+                        Err(_) => {
+                            let message = format!("`{}` is not an integer", token);
+                            return Err(error(start, words.len(), message));
+                        }
+                    }
+                    separated = false;
+                }
+            }
+            separated = true;
+        }
+        Ok(Program::from_words(words))
+    }
+}
+
+impl Program {
+    // Parses `program`, panicking if it isn't valid. See `FromStr` for
+    // what's accepted.
+    pub fn parse(program: &str) -> Program {
+        match program.parse() {
+            Ok(program) => program,
+            Err(error) => panic!("invalid program: {}", error),
+        }
+    }
+
+    // Reads a program, reporting parse errors as `InvalidData` errors
+    // wrapping a `ParseError`.
+    pub fn from_reader<R: Read>(mut reader: R) -> io::Result<Program> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        source
+            .parse()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Program> {
+        Program::from_reader(File::open(path)?)
+    }
+
+    pub fn from_stdin() -> io::Result<Program> {
+        Program::from_reader(io::stdin())
+    }
+
+    pub fn from_words(words: Vec<i64>) -> Program {
+        let memory = Memory::from_words(&words);
+        Program {
+            image: Arc::new(Image {
+                words,
+                memory,
+                fusion: OnceLock::new(),
+                #[cfg(feature = "jit")]
+                jit: OnceLock::new(),
+            }),
+        }
+    }
+
+    pub fn words(&self) -> &[i64] {
+        &self.image.words
+    }
+
+    pub(crate) fn memory(&self) -> Memory {
+        self.image.memory.clone()
+    }
+
+    // The program's fused handlers, found the first time they're needed.
+    pub(crate) fn fusion(&self) -> Arc<Fusion> {
+        let image = &self.image;
+        image
+            .fusion
+            .get_or_init(|| Arc::new(Fusion::new(&image.words)))
+            .clone()
+    }
+
+    // The program's compiled code, which is only set up if `create` is
+    // set or it already has been.
+    #[cfg(feature = "jit")]
+    pub(crate) fn jit(&self, create: bool) -> Option<&Mutex<Jit>> {
+        if create {
+            return Some(self.image.jit.get_or_init(|| Mutex::new(Jit::new())));
+        }
+        self.image.jit.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let parse = |source: &str| source.parse::<Program>().map(|p| p.words().to_vec());
+        assert!(parse("1,0,0,0,99").unwrap() == vec![1, 0, 0, 0, 99]);
+        assert!(parse(" 1 , -2,\t3\n").unwrap() == vec![1, -2, 3]);
+        assert!(parse("1,2,\r\n3\n4, # four\n# done\n").unwrap() == vec![1, 2, 3, 4]);
+        assert!(parse("").unwrap().is_empty());
+
+        let error = |line, column, index, message: &str| {
+            Err(ParseError {
+                index,
+                line,
+                column,
+                message: message.to_string(),
+            })
+        };
+        assert!(parse("1,2,x3,4") == error(1, 5, 2, "`x3` is not an integer"));
+        assert!(parse("1,2\n 3,,4") == error(2, 4, 3, "missing value"));
+        assert!(parse("1 2") == error(1, 3, 1, "expected `,` before `2`"));
+        // Columns count characters, not bytes.
+        assert!(parse("1,\u{a0}x") == error(1, 4, 1, "`x` is not an integer"));
+        assert!(
+            parse("99999999999999999999")
+                == error(1, 1, 0, "`99999999999999999999` is not an integer")
+        );
+        assert!(
+            parse("1,2,x3").unwrap_err().to_string()
+                == "line 1, column 5: `x3` is not an integer (value 2)"
+        );
+
+        let error = Program::from_reader("1,?".as_bytes()).unwrap_err();
+        assert!(error.kind() == io::ErrorKind::InvalidData);
+        assert!(error.to_string() == "line 1, column 3: `?` is not an integer (value 1)");
+        let filename = format!("{}/../day02/input", env!("CARGO_MANIFEST_DIR"));
+        assert!(Program::from_file(filename).unwrap().words()[..4] == [1, 0, 0, 3]);
+    }
+}
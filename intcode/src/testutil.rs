@@ -0,0 +1,84 @@
+// Helpers shared by the tests that check different ways of running a
+// program agree with each other, and by those that read traces.
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::computer::{IntCodeComputer, RunState};
+use crate::error::IntcodeError;
+use crate::program::Program;
+use crate::trace::TraceRecord;
+
+// Loads a day's puzzle input.
+pub(crate) fn day(name: &str) -> Program {
+    let filename = format!("{}/../{}/input", env!("CARGO_MANIFEST_DIR"), name);
+    Program::from_file(filename).unwrap()
+}
+
+// Everything a run can be observed by.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Outcome {
+    pub(crate) output: Vec<i64>,
+    pub(crate) error: Option<IntcodeError>,
+    pc: i64,
+    relative_base: i64,
+    instructions: u64,
+    code_writes: u64,
+    pub(crate) memory: Vec<i64>,
+}
+
+// Runs `computer` until it halts, fails or wants more input than `input`
+// holds.
+pub(crate) fn run(computer: &mut IntCodeComputer, input: &[i64]) -> Outcome {
+    let mut input = input.iter();
+    let mut output = Vec::new();
+    let mut error = None;
+    loop {
+        match computer.run() {
+            Ok(RunState::Output(value)) => output.push(value),
+            Ok(RunState::NeedsInput) => match input.next() {
+                Some(value) => computer.push_input(*value),
+                None => break,
+            },
+            Ok(RunState::Halted) => break,
+            Err(e) => {
+                error = Some(e);
+                break;
+            }
+        }
+    }
+    Outcome {
+        output,
+        error,
+        pc: computer.pc(),
+        relative_base: computer.relative_base(),
+        instructions: computer.stats().instructions,
+        code_writes: computer.stats().code_writes,
+        memory: (0..4096).map(|address| computer.read(address)).collect(),
+    }
+}
+
+// A trace writer whose records can be read back once the trace is finished.
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub(crate) fn records(&self) -> Vec<TraceRecord> {
+        let trace = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+        trace
+            .lines()
+            .map(|line| TraceRecord::from_json(line).unwrap())
+            .collect()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::instruction::{Mode, Opcode};
+
+// One executed instruction. `args` holds the resolved value of each
+// parameter, except that write targets hold the address written to.
+// `relative_base` is the base in effect when the instruction ran.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceRecord {
+    pub pc: i64,
+    pub opcode: Opcode,
+    pub modes: Vec<Mode>,
+    pub args: Vec<i64>,
+    pub writes: Vec<MemoryWrite>,
+    pub relative_base: i64,
+    pub input: Option<i64>,
+    pub output: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryWrite {
+    pub address: i64,
+    pub old: i64,
+    pub new: i64,
+}
+
+// Where the first difference between two traces shows up. `index` is the
+// 0-based record number; a missing record means that trace ended early.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub index: usize,
+    pub left: Option<TraceRecord>,
+    pub right: Option<TraceRecord>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "traces diverge at record {}", self.index)?;
+        for (side, record) in [("left", &self.left), ("right", &self.right)].iter() {
+            match record {
+                Some(record) => writeln!(f, "  {}: {}", side, record.to_json())?,
+                None => writeln!(f, "  {}: <end of trace>", side)?,
+            }
+        }
+        if let (Some(left), Some(right)) = (&self.left, &self.right) {
+            write!(
+                f,
+                "  differing fields: {}",
+                left.differing_fields(right).join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl TraceRecord {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn from_json(line: &str) -> serde_json::Result<TraceRecord> {
+        serde_json::from_str(line)
+    }
+
+    pub fn differing_fields(&self, other: &TraceRecord) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.pc != other.pc {
+            fields.push("pc");
+        }
+        if self.opcode != other.opcode {
+            fields.push("opcode");
+        }
+        if self.modes != other.modes {
+            fields.push("modes");
+        }
+        if self.args != other.args {
+            fields.push("args");
+        }
+        if self.writes != other.writes {
+            fields.push("writes");
+        }
+        if self.relative_base != other.relative_base {
+            fields.push("relative_base");
+        }
+        if self.input != other.input {
+            fields.push("input");
+        }
+        if self.output != other.output {
+            fields.push("output");
+        }
+        fields
+    }
+}
+
+// Compares two traces record by record, returning the first place they
+// differ, or `None` if they're identical.
+pub fn diff_traces<L, R>(left: L, right: R) -> Option<Divergence>
+where
+    L: IntoIterator<Item = TraceRecord>,
+    R: IntoIterator<Item = TraceRecord>,
+{
+    let mut left = left.into_iter();
+    let mut right = right.into_iter();
+    let mut index = 0;
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => return None,
+            (Some(l), Some(r)) if l == r => index += 1,
+            (l, r) => {
+                return Some(Divergence {
+                    index,
+                    left: l,
+                    right: r,
+                })
+            }
+        }
+    }
+}
+
+// Writes trace records as JSON lines. Write errors are held on to and
+// reported by `finish` so that tracing doesn't change how the VM fails.
+pub(crate) struct Tracer {
+    writer: Box<dyn Write + Send>,
+    error: Option<io::Error>,
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl Tracer {
+    pub(crate) fn new(writer: Box<dyn Write + Send>) -> Tracer {
+        Tracer {
+            writer,
+            error: None,
+        }
+    }
+
+    pub(crate) fn record(&mut self, record: &TraceRecord) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(error) = writeln!(self.writer, "{}", record.to_json()) {
+            self.error = Some(error);
+        }
+    }
+
+    pub(crate) fn finish(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let record = TraceRecord {
+            pc: 4,
+            opcode: Opcode::Add,
+            modes: vec![Mode::Relative, Mode::Immediate, Mode::Position],
+            args: vec![7, 1, 100],
+            writes: vec![MemoryWrite {
+                address: 100,
+                old: 0,
+                new: 8,
+            }],
+            relative_base: 3,
+            input: None,
+            output: None,
+        };
+        let json = record.to_json();
+        assert!(
+            json == "{\"pc\":4,\"opcode\":\"add\",\"modes\":[\"relative\",\"immediate\",\"position\"],\
+                     \"args\":[7,1,100],\"writes\":[{\"address\":100,\"old\":0,\"new\":8}],\
+                     \"relative_base\":3,\"input\":null,\"output\":null}"
+        );
+        assert!(TraceRecord::from_json(&json).unwrap() == record);
+
+        let mut other = record.clone();
+        other.args[0] = 6;
+        other.writes[0].new = 7;
+        assert!(record.differing_fields(&other) == vec!["args", "writes"]);
+
+        let left = vec![record.clone(), record.clone()];
+        let right = vec![record.clone(), other.clone()];
+        let divergence = diff_traces(left.clone(), right).unwrap();
+        assert!(divergence.index == 1);
+        assert!(divergence.right == Some(other));
+        assert!(diff_traces(left.clone(), left.clone()).is_none());
+        let divergence = diff_traces(left, vec![record]).unwrap();
+        assert!(divergence.index == 1 && divergence.right.is_none());
+    }
+}